@@ -82,13 +82,48 @@ pub enum Action {
     SpawnPopup,
     UpdateNames,   // Update filenames according to template string
     TemplatePopup, // Spawn template popup
+    TagsFromName,  // Fill frames from filenames using a pattern
+    NumberTracks,  // Number selected files in list order
+    FindReplace,   // Find and replace text in the frames of selected files
+    // Text transforms applied to the frames of selected files
+    TitleCase,
+    SentenceCase,
+    UpperCase,
+    LowerCase,
+    TrimWhitespace,
+    CollapseSpaces,
+    NormaliseUnicode,
+    CopyFrame,       // Copy the highlighted frame to the clipboard
+    CopyTag,         // Copy chosen frames or the whole tag of the highlighted file
+    PasteMerge,      // Add clipboard frames to selected files, replacing frames with the same id
+    PasteOverwrite,  // Replace the text frames of selected files with the clipboard
+    ToggleTableView, // Switch between the lists and the table view
+    ColumnPrev,
+    ColumnNext,
+    SortColumn, // Sort files by the highlighted table column
+    SortFiles,
+    Search,
+    FilterFiles,
+    SearchNext,
+    SearchPrev,
+    StreamInfo, // Show the audio properties of the highlighted file
+    Verify,     // Check selected files for damaged tags and audio
+    ReplayGain, // Measure the loudness of selected files and add ReplayGain frames
+    FindDuplicates,
+    LintAlbum, // Check that the selected files form a consistent album
 
     // FilesState Actions
     AddAllFiles, // Add all files
     AddFile,     // Add highlighted file
     ParentDir,   // Move to parent directory
     EnterDir,
-    HiddenDir, // Toggle display of hidden directories
+    HiddenDir,    // Toggle display of hidden directories
+    TagsFromPath, // Add files below the current directory with frames from their paths
+    FuzzyFind,    // Filter the listing by typing part of a name
+    AddRecursive, // Add files in the current directory and all subdirectories
+    ToggleMark,   // Mark the highlighted file or directory to be added
+    MarkRange,
+    TogglePreview,
 
     // FramesState Actions
     AddFrame,
@@ -147,12 +182,46 @@ impl<'de> Visitor<'de> for ActionVisitor {
             "spawn_popup" => Ok(Action::SpawnPopup),
             "update_names" => Ok(Action::UpdateNames),
             "template_popup" => Ok(Action::TemplatePopup),
+            "tags_from_name" => Ok(Action::TagsFromName),
+            "number_tracks" => Ok(Action::NumberTracks),
+            "find_replace" => Ok(Action::FindReplace),
+            "title_case" => Ok(Action::TitleCase),
+            "sentence_case" => Ok(Action::SentenceCase),
+            "upper_case" => Ok(Action::UpperCase),
+            "lower_case" => Ok(Action::LowerCase),
+            "trim_whitespace" => Ok(Action::TrimWhitespace),
+            "collapse_spaces" => Ok(Action::CollapseSpaces),
+            "normalise_unicode" => Ok(Action::NormaliseUnicode),
+            "copy_frame" => Ok(Action::CopyFrame),
+            "copy_tag" => Ok(Action::CopyTag),
+            "paste_merge" => Ok(Action::PasteMerge),
+            "paste_overwrite" => Ok(Action::PasteOverwrite),
+            "toggle_table_view" => Ok(Action::ToggleTableView),
+            "column_prev" => Ok(Action::ColumnPrev),
+            "column_next" => Ok(Action::ColumnNext),
+            "sort_column" => Ok(Action::SortColumn),
+            "sort_files" => Ok(Action::SortFiles),
+            "search" => Ok(Action::Search),
+            "filter_files" => Ok(Action::FilterFiles),
+            "search_next" => Ok(Action::SearchNext),
+            "search_prev" => Ok(Action::SearchPrev),
+            "stream_info" => Ok(Action::StreamInfo),
+            "verify" => Ok(Action::Verify),
+            "replaygain" => Ok(Action::ReplayGain),
+            "find_duplicates" => Ok(Action::FindDuplicates),
+            "lint_album" => Ok(Action::LintAlbum),
             // FilesState Actions
             "add_file" => Ok(Action::AddFile),
             "add_all_files" => Ok(Action::AddAllFiles),
             "parent_directory" => Ok(Action::ParentDir),
             "enter_directory" => Ok(Action::EnterDir),
             "show_hidden" => Ok(Action::HiddenDir),
+            "tags_from_path" => Ok(Action::TagsFromPath),
+            "fuzzy_find" => Ok(Action::FuzzyFind),
+            "add_recursive" => Ok(Action::AddRecursive),
+            "toggle_mark" => Ok(Action::ToggleMark),
+            "mark_range" => Ok(Action::MarkRange),
+            "toggle_preview" => Ok(Action::TogglePreview),
             // FramesState Actions
             "add_frame" => Ok(Action::AddFrame),
             // Popup Actions
@@ -3,4 +3,10 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 pub struct General {
     pub template_string: String,
+    pub title_case_exceptions: Vec<String>,
+    pub transform_frames: Vec<String>,
+    pub table_columns: Vec<String>,
+    pub details_order: Vec<String>,
+    pub hidden_frames: Vec<String>,
+    pub recursive_depth: Option<usize>,
 }
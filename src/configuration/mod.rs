@@ -10,11 +10,13 @@ use tui::style::Color;
 pub mod actions;
 mod general;
 mod theme;
+pub mod validation;
 use actions::{Action, ActionMap};
 use general::General;
 use theme::Theme;
+use validation::{Validation, ValidationMode};
 
-const DEFAULT_CONFIG: &'static str = include_str!("../../default_config.toml");
+const DEFAULT_CONFIG: &str = include_str!("../../default_config.toml");
 
 // Intermediate config struct generated by serde.
 // If the ActionMap can be converted to HashMap<KeyCode, Vec<Action>>
@@ -24,6 +26,7 @@ pub struct IConfig {
     theme: Theme,
     actions: ActionMap,
     general: General,
+    validation: Validation,
 }
 
 // Final config that is actually used by the app.
@@ -34,6 +37,7 @@ pub struct Config {
     actions: HashMap<KeyCode, Vec<Action>>,
     keys: HashMap<Action, KeyCode>,
     general: General,
+    validation: Validation,
 }
 
 impl Config {
@@ -92,6 +96,10 @@ impl Config {
         self.theme.active_window_title.into()
     }
 
+    pub fn active_title(&self) -> Color {
+        self.theme.active_title.into()
+    }
+
     pub fn active_border(&self) -> Color {
         self.theme.active_border.into()
     }
@@ -143,6 +151,46 @@ impl Config {
     pub fn get_template_string(&self) -> String {
         self.general.template_string.clone()
     }
+
+    pub fn get_title_case_exceptions(&self) -> Vec<String> {
+        self.general.title_case_exceptions.clone()
+    }
+
+    pub fn get_transform_frames(&self) -> Vec<String> {
+        self.general.transform_frames.clone()
+    }
+
+    pub fn get_table_columns(&self) -> Vec<String> {
+        self.general.table_columns.clone()
+    }
+
+    pub fn get_details_order(&self) -> Vec<String> {
+        self.general.details_order.clone()
+    }
+
+    pub fn get_hidden_frames(&self) -> Vec<String> {
+        self.general.hidden_frames.clone()
+    }
+
+    pub fn get_recursive_depth(&self) -> Option<usize> {
+        self.general.recursive_depth
+    }
+
+    pub fn get_validation_mode(&self) -> ValidationMode {
+        self.validation.mode
+    }
+
+    pub fn get_required_frames(&self) -> Vec<String> {
+        self.validation.required_frames.clone()
+    }
+
+    pub fn get_frame_formats(&self) -> HashMap<String, String> {
+        self.validation.formats.clone()
+    }
+
+    pub fn get_max_lengths(&self) -> HashMap<String, usize> {
+        self.validation.max_lengths.clone()
+    }
 }
 
 pub fn get_config_file_string() -> Option<String> {
@@ -162,11 +210,7 @@ pub fn get_config_file_string() -> Option<String> {
 }
 
 pub fn get_config_dir() -> Option<PathBuf> {
-    if let Some(dirs) = ProjectDirs::from("rid3", "rid3", "rid3") {
-        Some(PathBuf::from(dirs.config_dir()))
-    } else {
-        None
-    }
+    ProjectDirs::from("rid3", "rid3", "rid3").map(|dirs| PathBuf::from(dirs.config_dir()))
 }
 
 // Convert the HashMap<Action, KeyCode> of the intermediate IConfig to
@@ -174,6 +218,7 @@ pub fn get_config_dir() -> Option<PathBuf> {
 fn get_config(i_conf: IConfig) -> Config {
     let general = i_conf.general;
     let theme = i_conf.theme;
+    let validation = i_conf.validation;
     let mut keys = HashMap::new();
 
     let mut actions: HashMap<KeyCode, Vec<Action>> = HashMap::new();
@@ -194,6 +239,7 @@ fn get_config(i_conf: IConfig) -> Config {
         actions,
         keys,
         general,
+        validation,
     }
 }
 
@@ -5,9 +5,9 @@ use tui::style::Color as TuiColor;
 #[derive(Debug, Clone, Copy)]
 pub struct Color(tui::style::Color);
 
-impl Into<TuiColor> for Color {
-    fn into(self) -> TuiColor {
-        self.0
+impl From<Color> for TuiColor {
+    fn from(val: Color) -> Self {
+        val.0
     }
 }
 
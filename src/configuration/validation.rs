@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+// What happens to a file that breaks a validation rule when tags are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    #[default]
+    Off,
+    // The file is written and the problems are reported
+    Warn,
+    // The tag of the file is not written
+    Block,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Validation {
+    pub mode: ValidationMode,
+    pub required_frames: Vec<String>,
+    // Regex the whole value of a frame must match, by frame id or name
+    pub formats: HashMap<String, String>,
+    // Maximum number of characters in a frame, by frame id or name
+    pub max_lengths: HashMap<String, usize>,
+}
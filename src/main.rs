@@ -5,7 +5,6 @@ use crossterm::{
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use futures;
 use log::{debug, warn, LevelFilter};
 use notify::{self, event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use tokio::{
@@ -26,8 +25,11 @@ use configuration::{get_config_dir, Config};
 use logger::Logger;
 use render::{files_render::files_render, frames_render::frames_render, main_render::main_render};
 use state::{
-    files_state::FilesState, frames_state::FramesState, main_state::MainState, AppEvent,
-    ScreenState,
+    files_state::FilesState,
+    frames_state::FramesState,
+    jobs::{JobEvent, TagLoader},
+    main_state::{Entry, MainState},
+    AppEvent, ScreenState,
 };
 
 static LOGGER: Logger = Logger {
@@ -55,11 +57,15 @@ async fn main() -> Result<(), anyhow::Error> {
     let mut app_config = Config::new();
 
     let mut screen_state = ScreenState::Main;
-    let mut main_state = MainState::new(app_config.get_template_string());
-    let mut files_state = FilesState::new(dir)?;
+    let (job_tx, mut job_rx) = mpsc::unbounded_channel();
+    let mut main_state = MainState::new(app_config.get_template_string(), job_tx.clone());
+    let tag_loader = TagLoader::new(job_tx.clone());
+    let mut files_state = FilesState::new(dir, job_tx)?;
     let mut frames_state = FramesState::new();
     main_state.update_help_text(&app_config);
+    main_state.apply_config(&app_config);
     files_state.update_help_text(&app_config);
+    files_state.apply_config(&app_config);
     frames_state.update_help_text(&app_config);
 
     let mut show_logs = true;
@@ -207,7 +213,7 @@ async fn main() -> Result<(), anyhow::Error> {
                     ScreenState::Files => match files_state.handle_input(&key, &actions, &mut show_logs){
                         AppEvent::Quit => break,
                         AppEvent::SwitchScreen(s) => screen_state = s,
-                        AppEvent::AddFiles(files) => main_state.add_files(files),
+                        AppEvent::AddFiles(files) => tag_loader.load(main_state.add_files(files), None),
                         _ => {}
                     }
                     ScreenState::Frames => match frames_state.handle_input(&key, &actions, &mut show_logs) {
@@ -219,24 +225,67 @@ async fn main() -> Result<(), anyhow::Error> {
                 }
             }
             watcher_event = config_rx.recv() => {
-                if let Some(event) = watcher_event {
-                    if let Ok(e) = event {
-                        // Different text editors emit many different events when a file is updated
-                        // so matching for the `Data()` ModifyKind here should prevent most unnecessary
-                        // config rebuilds
-                        //
-                        // TODO - When using some editors the new config is rebuilt too quickly (before
-                        // the new data has actually been written to file). Need to fix this...
-                        match e.kind {
-                            EventKind::Modify(ModifyKind::Data(_)) => {
-                                app_config = Config::new();
-                                main_state.update_help_text(&app_config);
-                                files_state.update_help_text(&app_config);
-                                frames_state.update_help_text(&app_config);
-                            },
-                            _ => {}
+                if let Some(Ok(e)) = watcher_event {
+                    // Different text editors emit many different events when a file is updated
+                    // so matching for the `Data()` ModifyKind here should prevent most unnecessary
+                    // config rebuilds
+                    //
+                    // TODO - When using some editors the new config is rebuilt too quickly (before
+                    // the new data has actually been written to file). Need to fix this...
+                    if let EventKind::Modify(ModifyKind::Data(_)) = e.kind {
+                        app_config = Config::new();
+                        main_state.update_help_text(&app_config);
+                        main_state.apply_config(&app_config);
+                        files_state.update_help_text(&app_config);
+                        files_state.apply_config(&app_config);
+                        frames_state.update_help_text(&app_config);
+                    }
+                }
+            }
+            job_event = job_rx.recv() => {
+                // Handle every waiting event before rendering again
+                let mut job_event = job_event;
+                while let Some(event) = job_event {
+                    match event {
+                        JobEvent::Found(paths, progress) => {
+                            if progress.is_cancelled() {
+                                files_state.finish_job();
+                            } else {
+                                let entries = paths.into_iter().map(Entry::loading).collect();
+                                tag_loader.load(main_state.add_files(entries), Some(progress));
+                            }
+                        }
+                        JobEvent::Loaded(path, tag, info) => {
+                            if tag.is_none() {
+                                show_logs = true;
+                            }
+                            main_state.finish_loading(path, tag, info);
+                        }
+                        JobEvent::Cancelled(path) => main_state.cancel_loading(path),
+                        JobEvent::Preview(path, preview) => files_state.set_preview(path, preview),
+                        JobEvent::Verified(results) => {
+                            if main_state.finish_verify(results) {
+                                show_logs = true;
+                            }
+                        }
+                        JobEvent::Analysed(results) => {
+                            if main_state.finish_replaygain(results) {
+                                show_logs = true;
+                            }
+                        }
+                        JobEvent::Hashed(hashes) => {
+                            if main_state.finish_hash_audio(hashes) {
+                                show_logs = true;
+                            }
+                        }
+                        JobEvent::Finished(failed) => {
+                            files_state.finish_job();
+                            if failed > 0 {
+                                show_logs = true;
+                            }
                         }
                     }
+                    job_event = job_rx.try_recv().ok();
                 }
             }
             _ = timer_rx.recv() => { /* Nothing to do, just proceed to next loop iteration */ }
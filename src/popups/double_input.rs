@@ -144,7 +144,7 @@ impl Popup for DoubleInput {
         AppEvent::None
     }
 
-    fn get_widget(&self, config: &Config) -> PopupRender {
+    fn get_widget(&self, config: &Config) -> PopupRender<'_> {
         let text_one = format!("┳ {}\n┗ {}\n", "Description", self.description);
         let text_two = format!("┳ {}\n┗ {}\n", "Value", self.value);
         let items = vec![ListItem::new(text_one), ListItem::new(text_two)];
@@ -0,0 +1,274 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::{
+    text::Span,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use crate::{
+    configuration::{actions::Action, Config},
+    popups::{Popup, PopupData, PopupRender},
+    render::{active_border, active_title, active_window_title, basic, border, list_active},
+    state::AppEvent,
+    util,
+};
+
+// Identifies which form returned its values to the state that spawned it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormKind {
+    TagsFromName,
+    TagsFromPath,
+    NumberTracks,
+    FindReplace,
+    CopyFrames,
+    SortFiles,
+    Duplicates,
+}
+
+#[derive(Debug, Clone)]
+pub enum FormValue {
+    Text(String),
+    Toggle(bool),
+    // Index of the chosen option
+    Choice(usize),
+}
+
+impl FormValue {
+    pub fn text(&self) -> &str {
+        match self {
+            FormValue::Text(text) => text,
+            _ => "",
+        }
+    }
+
+    pub fn toggle(&self) -> bool {
+        match self {
+            FormValue::Toggle(b) => *b,
+            _ => false,
+        }
+    }
+
+    pub fn choice(&self) -> usize {
+        match self {
+            FormValue::Choice(i) => *i,
+            _ => 0,
+        }
+    }
+}
+
+pub struct FormField {
+    name: String,
+    value: FormValue,
+    // The options of a choice field, empty for other fields
+    options: Vec<String>,
+}
+
+impl FormField {
+    pub fn text(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            value: FormValue::Text(value.to_owned()),
+            options: vec![],
+        }
+    }
+
+    pub fn toggle(name: &str, value: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            value: FormValue::Toggle(value),
+            options: vec![],
+        }
+    }
+
+    pub fn choice(name: &str, options: Vec<String>, value: usize) -> Self {
+        Self {
+            name: name.to_owned(),
+            value: FormValue::Choice(value.min(options.len().saturating_sub(1))),
+            options,
+        }
+    }
+}
+
+// A popup with any number of text, toggle and choice fields. Text fields are edited
+// in the input box in the same way as the `DoubleInput` popup, toggles are flipped
+// and choices cycled with the `SelectField` action. All values are returned on
+// `SaveChanges`.
+pub struct FormPopup {
+    title: String,
+    kind: FormKind,
+    fields: Vec<FormField>,
+    input: String,
+    list_state: ListState,
+    cursor_pos: usize,
+    input_focused: bool,
+}
+
+impl FormPopup {
+    pub fn new(title: &str, kind: FormKind, fields: Vec<FormField>) -> Self {
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+
+        Self {
+            title: title.to_owned(),
+            kind,
+            fields,
+            input: "".to_owned(),
+            list_state,
+            cursor_pos: 0,
+            input_focused: false,
+        }
+    }
+
+    fn next(&mut self) {
+        let i = match self.list_state.selected() {
+            Some(i) => util::next(i, self.fields.len()),
+            None => 0,
+        };
+        self.list_state.select(Some(i));
+    }
+
+    fn prev(&mut self) {
+        let i = match self.list_state.selected() {
+            Some(i) => util::prev(i, self.fields.len()),
+            None => 0,
+        };
+        self.list_state.select(Some(i));
+    }
+
+    fn select_field(&mut self) {
+        if let Some(i) = self.list_state.selected() {
+            let field = &mut self.fields[i];
+            match &mut field.value {
+                FormValue::Text(text) => {
+                    self.input = text.clone();
+                    self.cursor_pos = self.input.chars().count();
+                    self.input_focused = true;
+                }
+                FormValue::Toggle(b) => *b = !*b,
+                FormValue::Choice(c) => *c = util::next(*c, field.options.len()),
+            }
+        }
+    }
+
+    // Byte index of the cursor, `cursor_pos` counts chars so that multi-byte
+    // characters can be entered
+    fn cursor_byte_index(&self) -> usize {
+        self.input
+            .char_indices()
+            .nth(self.cursor_pos)
+            .map(|(i, _)| i)
+            .unwrap_or(self.input.len())
+    }
+
+    fn increment_cursor_pos(&mut self) {
+        if self.cursor_pos < self.input.chars().count() {
+            self.cursor_pos += 1;
+        }
+    }
+
+    fn decrement_cursor_pos(&mut self) {
+        if self.cursor_pos > 0 {
+            self.cursor_pos -= 1;
+        }
+    }
+}
+
+impl Popup for FormPopup {
+    fn handle_input(&mut self, key: &KeyEvent, action: Action) -> AppEvent {
+        if !self.input_focused {
+            match action {
+                Action::Back => return AppEvent::ClosePopup,
+                Action::Prev => self.prev(),
+                Action::Next => self.next(),
+                Action::SelectField => self.select_field(),
+                Action::SaveChanges => {
+                    let values = self.fields.iter().map(|f| f.value.clone()).collect();
+                    return AppEvent::ClosePopupData(PopupData::Form(self.kind, values));
+                }
+                _ => {}
+            }
+        } else {
+            match key.code {
+                KeyCode::Esc => {
+                    self.input.clear();
+                    self.cursor_pos = 0;
+                    self.input_focused = false;
+                }
+                KeyCode::Backspace if self.cursor_pos > 0 => {
+                    self.decrement_cursor_pos();
+                    let i = self.cursor_byte_index();
+                    self.input.remove(i);
+                }
+                KeyCode::Left => self.decrement_cursor_pos(),
+                KeyCode::Right => self.increment_cursor_pos(),
+                KeyCode::Enter => {
+                    if let Some(i) = self.list_state.selected() {
+                        self.fields[i].value = FormValue::Text(self.input.clone());
+                    }
+                    self.input.clear();
+                    self.cursor_pos = 0;
+                    self.input_focused = false;
+                }
+                KeyCode::Char(c) => {
+                    let i = self.cursor_byte_index();
+                    self.input.insert(i, c);
+                    self.increment_cursor_pos();
+                }
+                _ => {}
+            }
+        }
+        AppEvent::None
+    }
+
+    fn get_widget(&self, config: &Config) -> PopupRender<'_> {
+        let items: Vec<ListItem> = self
+            .fields
+            .iter()
+            .map(|field| {
+                let value = match &field.value {
+                    FormValue::Text(text) => text.clone(),
+                    FormValue::Toggle(true) => "Yes".to_owned(),
+                    FormValue::Toggle(false) => "No".to_owned(),
+                    FormValue::Choice(i) => field.options.get(*i).cloned().unwrap_or_default(),
+                };
+                ListItem::new(format!("┳ {}\n┗ {}\n", field.name, value))
+            })
+            .collect();
+
+        let (list_title_style, title_style, border_style) = match self.input_focused {
+            true => (
+                active_title(config),
+                active_window_title(config),
+                active_border(config),
+            ),
+            false => (
+                active_window_title(config),
+                active_title(config),
+                border(config),
+            ),
+        };
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(Span::styled(self.title.clone(), list_title_style))
+                    .style(border(config))
+                    .borders(Borders::ALL),
+            )
+            .style(basic(config))
+            .highlight_style(list_active(config));
+
+        let input_block = Paragraph::new(Span::styled(&self.input, basic(config))).block(
+            Block::default()
+                .title(Span::styled("Input", title_style))
+                .style(border_style)
+                .borders(Borders::ALL),
+        );
+
+        PopupRender::Form((
+            list,
+            input_block,
+            self.list_state.clone(),
+            self.input_focused.then_some(self.cursor_pos),
+        ))
+    }
+}
@@ -28,7 +28,7 @@ impl Popup for HelpPopup {
         AppEvent::ClosePopup
     }
 
-    fn get_widget(&self, config: &Config) -> PopupRender {
+    fn get_widget(&self, config: &Config) -> PopupRender<'_> {
         let items: Vec<ListItem> = self
             .data
             .iter()
@@ -1,27 +1,39 @@
+use std::sync::Arc;
+
 use crossterm::event::KeyEvent;
-use tui::widgets::{List, ListState, Paragraph};
+use tui::widgets::{Gauge, List, ListState, Paragraph, Table, TableState};
 
 use crate::{
     configuration::{actions::Action, Config},
-    state::AppEvent,
+    state::{jobs::Progress, AppEvent},
 };
 
 pub mod double_input;
+pub mod form;
 pub mod help;
+pub mod progress;
 pub mod single_input;
+pub mod table;
 pub mod template;
+use form::{FormKind, FormValue};
+use table::TableKind;
 
 pub enum PopupRender<'a> {
     Help(List<'a>),
     SingleInput((List<'a>, Paragraph<'a>, ListState, usize)),
     DoubleInput((List<'a>, Paragraph<'a>, ListState, usize)),
     TemplateInput((List<'a>, Paragraph<'a>, usize)),
+    Form((List<'a>, Paragraph<'a>, ListState, Option<usize>)),
+    Table((Table<'a>, TableState)),
+    Progress(Gauge<'a>),
 }
 
 pub enum PopupData {
     SingleInput(String),
     DoubleInput(String, String),
     TemplateInput(String),
+    Form(FormKind, Vec<FormValue>),
+    Table(TableKind, Option<usize>),
 }
 
 pub enum PopupHelpType {
@@ -30,5 +42,10 @@ pub enum PopupHelpType {
 
 pub trait Popup {
     fn handle_input(&mut self, key: &KeyEvent, action: Action) -> AppEvent;
-    fn get_widget(&self, config: &Config) -> PopupRender;
+    fn get_widget(&self, config: &Config) -> PopupRender<'_>;
+
+    // The background job whose progress the popup shows
+    fn progress(&self) -> Option<&Arc<Progress>> {
+        None
+    }
 }
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use crossterm::event::KeyEvent;
+use tui::{
+    text::Span,
+    widgets::{Block, Borders, Gauge},
+};
+
+use crate::{
+    configuration::{actions::Action, Config},
+    popups::{Popup, PopupRender},
+    render::{border, list_active, window_title},
+    state::{jobs::Progress, AppEvent},
+};
+
+// Shows the progress of a background job, `Back` cancels the job. The popup is
+// removed by the state that spawned it when the job finishes.
+pub struct ProgressPopup {
+    title: String,
+    progress: Arc<Progress>,
+}
+
+impl ProgressPopup {
+    pub fn new(title: &str, progress: Arc<Progress>) -> Self {
+        Self {
+            title: title.to_owned(),
+            progress,
+        }
+    }
+}
+
+impl Popup for ProgressPopup {
+    fn handle_input(&mut self, _key: &KeyEvent, action: Action) -> AppEvent {
+        match action {
+            Action::Back => {
+                self.progress.cancel();
+                AppEvent::ClosePopup
+            }
+            _ => AppEvent::None,
+        }
+    }
+
+    fn get_widget(&self, config: &Config) -> PopupRender<'_> {
+        let (done, total) = (self.progress.done(), self.progress.total());
+        let (label, ratio) = match total {
+            0 => ("Searching for files...".to_owned(), 0.0),
+            _ => (
+                format!("{} of {} files - Esc to cancel", done, total),
+                done as f64 / total as f64,
+            ),
+        };
+
+        PopupRender::Progress(
+            Gauge::default()
+                .block(
+                    Block::default()
+                        .title(Span::styled(self.title.clone(), window_title(config)))
+                        .style(border(config))
+                        .borders(Borders::ALL),
+                )
+                .gauge_style(list_active(config))
+                .label(label)
+                .ratio(ratio.min(1.0)),
+        )
+    }
+
+    fn progress(&self) -> Option<&Arc<Progress>> {
+        Some(&self.progress)
+    }
+}
@@ -72,7 +72,7 @@ impl Popup for SingleInput {
         AppEvent::None
     }
 
-    fn get_widget(&self, config: &Config) -> PopupRender {
+    fn get_widget(&self, config: &Config) -> PopupRender<'_> {
         let text = format!("┳ {}\n┗ {}\n", self.text, self.content);
         let items = vec![ListItem::new(text)];
 
@@ -0,0 +1,130 @@
+use crossterm::event::KeyEvent;
+use tui::{
+    layout::Constraint,
+    text::Span,
+    widgets::{Block, Borders, Cell, Row, Table, TableState},
+};
+
+use crate::{
+    configuration::{actions::Action, Config},
+    popups::{Popup, PopupData, PopupRender},
+    render::{basic, border, list_active, secondary_title, window_title},
+    state::AppEvent,
+    util,
+};
+
+// Identifies what the rows of a table popup refer to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    // The rows preview pending changes which are applied on confirmation
+    Preview,
+    // The rows are only shown, nothing happens when the popup is closed
+    Info,
+    // Each row is a group of duplicate files, confirming removes the extra copies of
+    // the highlighted group
+    Duplicates,
+    // Each row is a problem found by the album linter, confirming applies the fix of
+    // the highlighted problem
+    Lint,
+}
+
+// Popup displaying rows of text in columns, closed with `SaveChanges` or `SelectField`
+// to confirm or `Back` to cancel. Confirming returns the highlighted row.
+pub struct TablePopup {
+    title: String,
+    kind: TableKind,
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+    widths: Vec<Constraint>,
+    table_state: TableState,
+}
+
+impl TablePopup {
+    pub fn new(title: &str, kind: TableKind, header: Vec<String>, rows: Vec<Vec<String>>) -> Self {
+        let mut table_state = TableState::default();
+        if !rows.is_empty() {
+            table_state.select(Some(0));
+        }
+
+        let columns = header.len().max(1) as u16;
+        let widths = (0..columns)
+            .map(|_| Constraint::Percentage(100 / columns))
+            .collect();
+
+        Self {
+            title: title.to_owned(),
+            kind,
+            header,
+            rows,
+            widths,
+            table_state,
+        }
+    }
+
+    fn next(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let i = match self.table_state.selected() {
+            Some(i) => util::next(i, self.rows.len()),
+            None => 0,
+        };
+        self.table_state.select(Some(i));
+    }
+
+    fn prev(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let i = match self.table_state.selected() {
+            Some(i) => util::prev(i, self.rows.len()),
+            None => 0,
+        };
+        self.table_state.select(Some(i));
+    }
+}
+
+impl Popup for TablePopup {
+    fn handle_input(&mut self, _key: &KeyEvent, action: Action) -> AppEvent {
+        match action {
+            Action::Back => return AppEvent::ClosePopup,
+            Action::Prev => self.prev(),
+            Action::Next => self.next(),
+            Action::SelectField | Action::SaveChanges => {
+                return AppEvent::ClosePopupData(PopupData::Table(
+                    self.kind,
+                    self.table_state.selected(),
+                ))
+            }
+            _ => {}
+        }
+        AppEvent::None
+    }
+
+    fn get_widget(&self, config: &Config) -> PopupRender<'_> {
+        let header = Row::new(
+            self.header
+                .iter()
+                .map(|h| Cell::from(h.as_str()).style(secondary_title(config))),
+        );
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| Row::new(row.iter().map(|cell| Cell::from(cell.as_str()))));
+
+        let table = Table::new(rows)
+            .header(header.bottom_margin(1))
+            .block(
+                Block::default()
+                    .title(Span::styled(self.title.clone(), window_title(config)))
+                    .style(border(config))
+                    .borders(Borders::ALL),
+            )
+            .style(basic(config))
+            .highlight_style(list_active(config))
+            .widths(&self.widths)
+            .column_spacing(1);
+
+        PopupRender::Table((table, self.table_state.clone()))
+    }
+}
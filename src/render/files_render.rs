@@ -1,15 +1,19 @@
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout},
-    terminal::Terminal,
-    text::Span,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    terminal::{Frame, Terminal},
+    text::{Span, Spans},
     widgets::{Block, Borders, List, ListItem},
 };
 
 use crate::{
     configuration::Config,
     logger::Logger,
-    render::{basic, border, list_active, list_directory, render_logs, render_popup, window_title},
+    render::{
+        basic, border, list_active, list_directory, list_highlighted, render_logs, render_popup,
+        window_title,
+    },
     state::files_state::{FilesState, FilesStateItem},
 };
 
@@ -38,42 +42,25 @@ where
                 .split(size)
         };
 
-        let mut items = vec![];
-        for entry in state.files.iter() {
-            let (text, style) = match entry {
-                FilesStateItem::DirEntry(entry) => (
-                    entry
-                        .file_name()
-                        .into_string()
-                        .expect("Could not parse OsString"),
-                    if entry.file_type().unwrap().is_dir() {
-                        list_directory(app_config)
-                    } else {
-                        basic(app_config)
-                    },
-                ),
-                FilesStateItem::Parent => ("../".to_owned(), list_directory(app_config)),
-            };
-
-            items.push(ListItem::new(text).style(style));
-        }
-
-        let title = if let Some(s) = state.current_dir.to_str() {
-            format!("Files - {}", s)
-        } else {
-            "Files".to_string()
+        let chunks_top = match state.show_preview {
+            true => Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                .split(chunks[0]),
+            false => Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(100), Constraint::Length(0)].as_ref())
+                .split(chunks[0]),
         };
 
-        let block = List::new(items)
-            .block(
-                Block::default()
-                    .title(Span::styled(title, window_title(app_config)))
-                    .style(border(app_config))
-                    .borders(Borders::ALL),
-            )
-            .highlight_style(list_active(app_config));
-
-        f.render_stateful_widget(block, chunks[0], &mut state.files_state);
+        if state.finder.is_some() {
+            render_finder(f, chunks_top[0], app_config, state);
+        } else {
+            render_listing(f, chunks_top[0], app_config, state);
+        }
+        if state.show_preview {
+            render_preview(f, chunks_top[1], app_config, state);
+        }
 
         // Logs
         if show_logs {
@@ -89,3 +76,123 @@ where
 
     Ok(())
 }
+
+// Tags of the highlighted file, read in the background the first time it is
+// highlighted
+fn render_preview<B>(f: &mut Frame<B>, area: Rect, app_config: &Config, state: &FilesState)
+where
+    B: Backend,
+{
+    let lines = match state.preview() {
+        Some(Some(preview)) => preview.lines(),
+        Some(None) => vec!["Loading...".to_owned()],
+        None => vec![],
+    };
+    let items: Vec<ListItem> = lines.into_iter().map(ListItem::new).collect();
+
+    let block = List::new(items)
+        .block(
+            Block::default()
+                .title(Span::styled("Preview", window_title(app_config)))
+                .style(border(app_config))
+                .borders(Borders::ALL),
+        )
+        .style(basic(app_config));
+    f.render_widget(block, area);
+}
+
+// Directories, including the parent entry, are styled apart from files in both the
+// listing and the finder. The file type is read with the directory so this doesn't
+// stat every entry on each render.
+fn item_style(item: &FilesStateItem, app_config: &Config) -> Style {
+    match item {
+        FilesStateItem::DirEntry(entry) if !entry.file_type().is_ok_and(|t| t.is_dir()) => {
+            basic(app_config)
+        }
+        _ => list_directory(app_config),
+    }
+}
+
+fn render_listing<B>(f: &mut Frame<B>, area: Rect, app_config: &Config, state: &mut FilesState)
+where
+    B: Backend,
+{
+    let mut items = vec![];
+    for entry in state.files.iter() {
+        // Marked items are shown the same way as selected files on the main screen
+        let style = match state.is_marked(entry) {
+            true => list_highlighted(app_config),
+            false => item_style(entry, app_config),
+        };
+        items.push(ListItem::new(entry.name()).style(style));
+    }
+
+    let mut title = if let Some(s) = state.current_dir.to_str() {
+        format!("Files - {}", s)
+    } else {
+        "Files".to_string()
+    };
+    if state.marked_count() > 0 {
+        title = format!("{} - {} marked", title, state.marked_count());
+    }
+
+    let block = List::new(items)
+        .block(
+            Block::default()
+                .title(Span::styled(title, window_title(app_config)))
+                .style(border(app_config))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(list_active(app_config));
+
+    f.render_stateful_widget(block, area, &mut state.files_state);
+}
+
+// The items matching the fuzzy finder query, best match first, with the matched
+// chars underlined
+fn render_finder<B>(f: &mut Frame<B>, area: Rect, app_config: &Config, state: &mut FilesState)
+where
+    B: Backend,
+{
+    let finder = match &mut state.finder {
+        Some(finder) => finder,
+        None => return,
+    };
+
+    let items: Vec<ListItem> = finder
+        .matches
+        .iter()
+        .map(|m| {
+            let item = &state.files[m.index];
+            let style = item_style(item, app_config);
+            let matched = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+            let spans: Vec<Span> = item
+                .name()
+                .chars()
+                .enumerate()
+                .map(|(i, c)| match m.positions.contains(&i) {
+                    true => Span::styled(c.to_string(), matched),
+                    false => Span::styled(c.to_string(), style),
+                })
+                .collect();
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    let title = format!(
+        "Find - {} of {} - {}",
+        finder.matches.len(),
+        state.files.len(),
+        finder.query
+    );
+    let block = List::new(items)
+        .block(
+            Block::default()
+                .title(Span::styled(title, window_title(app_config)))
+                .style(border(app_config))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(list_active(app_config));
+
+    f.render_stateful_widget(block, area, &mut finder.list_state);
+}
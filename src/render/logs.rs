@@ -18,9 +18,7 @@ pub fn render_logs<'a>(config: &Config, log_state: &Logger) -> List<'a> {
         .lock()
         .expect("Could not acquire lock")
         .len();
-    if last_index > 0 {
-        last_index -= 1;
-    }
+    last_index = last_index.saturating_sub(1);
 
     let log_items: Vec<ListItem> = if last_index == 0 {
         vec![]
@@ -1,16 +1,17 @@
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout},
-    terminal::Terminal,
+    layout::{Constraint, Direction, Layout, Rect},
+    terminal::{Frame, Terminal},
     text::Span,
-    widgets::{Block, Borders, List, ListItem},
+    widgets::{Block, Borders, Cell, List, ListItem, Row, Table},
 };
 
 use crate::{
     configuration::Config,
     logger::Logger,
     render::{
-        basic, border, list_active, list_highlighted, render_logs, render_popup, window_title,
+        basic, border, list_active, list_highlighted, render_logs, render_popup, secondary_title,
+        window_title,
     },
     state::main_state::{DetailItem, Focus, MainState},
 };
@@ -40,67 +41,11 @@ where
                 .split(size)
         };
 
-        let chunks_top = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
-            .split(chunks[0]);
-
-        // MainState files
-        let file_items: Vec<ListItem> = state
-            .files
-            .iter()
-            .map(|item| {
-                let text = item.filename.clone();
-
-                ListItem::new(text).style(match item.selected {
-                    true => list_highlighted(app_config),
-                    false => basic(app_config),
-                })
-            })
-            .collect();
-
-        let left_block = List::new(file_items)
-            .block(
-                Block::default()
-                    .title(Span::styled("Files", window_title(app_config)))
-                    .style(border(app_config))
-                    .borders(Borders::ALL),
-            )
-            .highlight_style(match state.focus {
-                Focus::Files => list_active(app_config),
-                _ => list_highlighted(app_config),
-            });
-
-        f.render_stateful_widget(left_block, chunks_top[0], &mut state.files_state);
-
-        // MainState details
-        let details: Vec<ListItem> = state
-            .details
-            .iter()
-            .map(|item| match item {
-                DetailItem::FileName(file_name) => {
-                    let text = format!("┳ Filename\n┗ {}\n", file_name);
-                    ListItem::new(text).style(basic(app_config))
-                }
-                DetailItem::Frame(frame) => {
-                    let text = format!("┳ {}\n┗ {}\n", frame.name(), frame.content());
-                    ListItem::new(text).style(basic(app_config))
-                }
-            })
-            .collect();
-
-        let right_block = List::new(details)
-            .block(
-                Block::default()
-                    .title(Span::styled("Details", window_title(app_config)))
-                    .style(border(app_config))
-                    .borders(Borders::ALL),
-            )
-            .highlight_style(match state.focus {
-                Focus::Details => list_active(app_config),
-                _ => list_highlighted(app_config),
-            });
-        f.render_stateful_widget(right_block, chunks_top[1], &mut state.details_state);
+        if state.table_view {
+            render_table(f, chunks[0], app_config, state);
+        } else {
+            render_lists(f, chunks[0], app_config, state);
+        }
 
         // Logs
         if show_logs {
@@ -116,3 +61,137 @@ where
 
     Ok(())
 }
+
+fn render_lists<B>(f: &mut Frame<B>, area: Rect, app_config: &Config, state: &mut MainState)
+where
+    B: Backend,
+{
+    let chunks_top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(area);
+
+    // MainState files
+    let file_items: Vec<ListItem> = state
+        .visible_files()
+        .map(|item| {
+            let text = match item.loading {
+                true => format!("⧗ {}", item.filename),
+                false => item.filename.clone(),
+            };
+
+            ListItem::new(text).style(match item.selected {
+                true => list_highlighted(app_config),
+                false => basic(app_config),
+            })
+        })
+        .collect();
+
+    let left_block = List::new(file_items)
+        .block(
+            Block::default()
+                .title(Span::styled(state.files_title(), window_title(app_config)))
+                .style(border(app_config))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(match state.focus {
+            Focus::Files => list_active(app_config),
+            _ => list_highlighted(app_config),
+        });
+
+    f.render_stateful_widget(left_block, chunks_top[0], &mut state.files_state);
+
+    // MainState details
+    let details: Vec<ListItem> = state
+        .details
+        .iter()
+        .map(|item| match item {
+            DetailItem::FileName(file_name) => {
+                let text = format!("┳ Filename\n┗ {}\n", file_name);
+                ListItem::new(text).style(basic(app_config))
+            }
+            DetailItem::Frame(frame) => {
+                let text = format!("┳ {}\n┗ {}\n", frame.name(), frame.content());
+                ListItem::new(text).style(basic(app_config))
+            }
+            DetailItem::Multiple(frame) => {
+                let text = format!("┳ {}\n┗ <multiple values>\n", frame.name());
+                ListItem::new(text).style(basic(app_config))
+            }
+        })
+        .collect();
+
+    let details_title = match state.merged_count {
+        0 => "Details".to_owned(),
+        n => format!("Details - {} files", n),
+    };
+    let right_block = List::new(details)
+        .block(
+            Block::default()
+                .title(Span::styled(details_title, window_title(app_config)))
+                .style(border(app_config))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(match state.focus {
+            Focus::Details => list_active(app_config),
+            _ => list_highlighted(app_config),
+        });
+    f.render_stateful_widget(right_block, chunks_top[1], &mut state.details_state);
+}
+
+// Every loaded file as a row with the configured columns. Styles are set per cell
+// rather than with `highlight_style` so the highlighted cell stands out within the
+// highlighted row.
+fn render_table<B>(f: &mut Frame<B>, area: Rect, app_config: &Config, state: &mut MainState)
+where
+    B: Backend,
+{
+    let header = Row::new(state.table_columns.iter().enumerate().map(|(i, column)| {
+        let arrow = match state.table_sort {
+            Some((c, false)) if c == i => " ▲",
+            Some((c, true)) if c == i => " ▼",
+            _ => "",
+        };
+        let style = match i == state.table_column {
+            true => list_active(app_config),
+            false => secondary_title(app_config),
+        };
+        Cell::from(format!("{}{}", column.title(), arrow)).style(style)
+    }));
+
+    let highlighted = state.files_state.selected();
+    let rows = state.visible_files().enumerate().map(|(row, entry)| {
+        let row_style = match (highlighted == Some(row), entry.selected) {
+            (true, _) | (false, true) => list_highlighted(app_config),
+            _ => basic(app_config),
+        };
+        let cells = state.table_columns.iter().enumerate().map(|(i, column)| {
+            let cell = Cell::from(column.value(entry));
+            match highlighted == Some(row) && i == state.table_column {
+                true => cell.style(list_active(app_config)),
+                false => cell,
+            }
+        });
+        Row::new(cells).style(row_style)
+    });
+
+    let columns = state.table_columns.len().max(1) as u32;
+    let widths: Vec<Constraint> = (0..columns)
+        .map(|_| Constraint::Ratio(1, columns))
+        .collect();
+
+    let table = Table::new(rows)
+        .header(header.bottom_margin(1))
+        .block(
+            Block::default()
+                .title(Span::styled(state.files_title(), window_title(app_config)))
+                .style(border(app_config))
+                .borders(Borders::ALL),
+        )
+        .style(basic(app_config))
+        .widths(&widths)
+        .column_spacing(1);
+
+    state.table_state.select(highlighted);
+    f.render_stateful_widget(table, area, &mut state.table_state);
+}
@@ -17,7 +17,7 @@ mod logs;
 pub mod main_render;
 use logs::render_logs;
 
-pub fn render_popup<B>(size: Rect, f: &mut Frame<B>, popup: &Box<dyn Popup>, config: &Config)
+pub fn render_popup<B>(size: Rect, f: &mut Frame<B>, popup: &dyn Popup, config: &Config)
 where
     B: Backend,
 {
@@ -45,9 +45,27 @@ where
         )
         .split(chunks_horizontal[1]);
 
-    f.render_widget(Clear, chunks_vertical[1]);
-
     let w = popup.get_widget(config);
+    if let PopupRender::Progress(gauge) = w {
+        // Progress bars only need a few lines in the middle of the popup area
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(3),
+                    Constraint::Min(0),
+                ]
+                .as_ref(),
+            )
+            .split(chunks_vertical[1]);
+
+        f.render_widget(Clear, chunks[1]);
+        f.render_widget(gauge, chunks[1]);
+        return;
+    }
+
+    f.render_widget(Clear, chunks_vertical[1]);
     match w {
         PopupRender::Help(help) => {
             f.render_widget(help, chunks_vertical[1]);
@@ -82,6 +100,22 @@ where
             f.render_widget(input, chunks[1]);
             f.set_cursor(chunks[1].x + cursor_pos as u16 + 1, chunks[1].y + 1);
         }
+        PopupRender::Form((list, input, mut state, cursor_pos)) => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+                .split(chunks_vertical[1]);
+
+            f.render_stateful_widget(list, chunks[0], &mut state);
+            f.render_widget(input, chunks[1]);
+            if let Some(cursor_pos) = cursor_pos {
+                f.set_cursor(chunks[1].x + cursor_pos as u16 + 1, chunks[1].y + 1);
+            }
+        }
+        PopupRender::Table((table, mut state)) => {
+            f.render_stateful_widget(table, chunks_vertical[1], &mut state);
+        }
+        PopupRender::Progress(_) => {}
     }
 }
 
@@ -106,6 +140,10 @@ pub fn secondary_title(config: &Config) -> Style {
     Style::default().fg(config.secondary_title())
 }
 
+pub fn active_title(config: &Config) -> Style {
+    Style::default().fg(config.active_title())
+}
+
 pub fn active_window_title(config: &Config) -> Style {
     Style::default().fg(config.active_window_title())
 }
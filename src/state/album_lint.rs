@@ -0,0 +1,507 @@
+use std::path::PathBuf;
+
+use id3::{Content, Frame, TagLike};
+
+use crate::state::main_state::Entry;
+
+// Frames that should have the same value in every file of an album
+const ALBUM_FRAMES: [(&str, &str); 4] = [
+    ("TALB", "Album"),
+    ("TPE2", "Album artist"),
+    ("TDRC", "Date"),
+    ("TCON", "Genre"),
+];
+
+// A problem found in an album, with the frames that fix it if the fix is unambiguous
+pub struct Finding {
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+pub struct Fix {
+    pub description: String,
+    // Path of a file and the frames to add to it
+    pub changes: Vec<(PathBuf, Vec<Frame>)>,
+}
+
+impl Finding {
+    fn new(message: String) -> Self {
+        Self { message, fix: None }
+    }
+
+    fn with_fix(message: String, description: String, changes: Vec<(PathBuf, Vec<Frame>)>) -> Self {
+        Self {
+            message,
+            fix: Some(Fix {
+                description,
+                changes,
+            }),
+        }
+    }
+}
+
+// Check that `entries` form a consistent album, tracks are checked separately for
+// each disc
+pub fn lint(entries: &[&Entry]) -> Vec<Finding> {
+    let mut findings = vec![];
+    for (id, name) in ALBUM_FRAMES {
+        check_consistent(entries, id, name, &mut findings);
+    }
+
+    let mut discs: Vec<(&str, Vec<&Entry>)> = vec![];
+    for entry in entries {
+        let disc = text(entry, "TPOS").map_or("", |d| d.split('/').next().unwrap().trim());
+        match discs.iter_mut().find(|(d, _)| *d == disc) {
+            Some((_, disc_entries)) => disc_entries.push(entry),
+            None => discs.push((disc, vec![entry])),
+        }
+    }
+    let multi_disc = discs.len() > 1;
+    for (disc, disc_entries) in &discs {
+        let prefix = match (multi_disc, disc.is_empty()) {
+            (false, _) => "".to_owned(),
+            (true, true) => "No disc: ".to_owned(),
+            (true, false) => format!("Disc {}: ", disc),
+        };
+        check_tracks(disc_entries, &prefix, &mut findings);
+    }
+
+    check_cover(entries, &mut findings);
+    for entry in entries {
+        if text(entry, "TIT2").is_none() {
+            findings.push(Finding::new(format!("{} has no title", entry.filename)));
+        }
+    }
+    findings
+}
+
+// Trimmed text of a frame, `None` if it is missing or empty
+fn text<'a>(entry: &'a Entry, id: &str) -> Option<&'a str> {
+    let text = entry.tag.get(id)?.content().text()?.trim();
+    (!text.is_empty()).then_some(text)
+}
+
+// Number of files with each value, in the order the values are first seen
+fn count<T: PartialEq>(values: impl Iterator<Item = T>) -> Vec<(T, usize)> {
+    let mut counts: Vec<(T, usize)> = vec![];
+    for value in values {
+        match counts.iter_mut().find(|(v, _)| *v == value) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    counts
+}
+
+fn files(n: usize) -> String {
+    match n {
+        1 => "1 file".to_owned(),
+        n => format!("{} files", n),
+    }
+}
+
+fn list_counts<T: ToString>(counts: &[(T, usize)]) -> String {
+    counts
+        .iter()
+        .map(|(value, n)| format!("\"{}\" ({})", value.to_string(), files(*n)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// A frame missing from some files can be fixed if every other file has the same value
+fn check_consistent(entries: &[&Entry], id: &str, name: &str, findings: &mut Vec<Finding>) {
+    let values = count(entries.iter().filter_map(|e| text(e, id)));
+    let missing: Vec<&PathBuf> = entries
+        .iter()
+        .filter(|e| text(e, id).is_none())
+        .map(|e| &e.path)
+        .collect();
+
+    match values.as_slice() {
+        [] => {}
+        [(value, _)] if !missing.is_empty() => {
+            let changes = missing
+                .iter()
+                .map(|path| ((*path).clone(), vec![Frame::text(id, *value)]))
+                .collect();
+            findings.push(Finding::with_fix(
+                format!("{} is missing in {}", name, files(missing.len())),
+                format!("Set {} to \"{}\"", name.to_lowercase(), value),
+                changes,
+            ));
+        }
+        [_] => {}
+        _ => {
+            let mut message = format!("{} differs: {}", name, list_counts(&values));
+            if !missing.is_empty() {
+                message.push_str(&format!(", missing in {}", files(missing.len())));
+            }
+            findings.push(Finding::new(message));
+        }
+    }
+}
+
+// Track numbers of one disc should run from 1 without gaps or repeats, and totals in
+// the `n/total` form should all be the number of tracks. Totals are only fixed once
+// the track numbers are complete.
+fn check_tracks(entries: &[&Entry], prefix: &str, findings: &mut Vec<Finding>) {
+    let mut tracks: Vec<(&Entry, u32, Option<u32>)> = vec![];
+    let mut complete = true;
+    for entry in entries {
+        let value = match text(entry, "TRCK") {
+            Some(value) => value,
+            None => {
+                findings.push(Finding::new(format!(
+                    "{}{} has no track number",
+                    prefix, entry.filename
+                )));
+                complete = false;
+                continue;
+            }
+        };
+        let (number, total) = match value.split_once('/') {
+            Some((n, t)) => (n.trim().parse().ok(), t.trim().parse().ok().map(Some)),
+            None => (value.parse().ok(), Some(None)),
+        };
+        match (number, total) {
+            (Some(number), Some(total)) if number > 0 => tracks.push((entry, number, total)),
+            _ => {
+                findings.push(Finding::new(format!(
+                    "{}{} has an invalid track number \"{}\"",
+                    prefix, entry.filename, value
+                )));
+                complete = false;
+            }
+        }
+    }
+
+    let numbers = count(tracks.iter().map(|(_, n, _)| *n));
+    for (number, n) in &numbers {
+        if *n > 1 {
+            let files: Vec<&str> = tracks
+                .iter()
+                .filter(|(_, t, _)| t == number)
+                .map(|(e, _, _)| e.filename.as_str())
+                .collect();
+            findings.push(Finding::new(format!(
+                "{}Track {} is used by {}",
+                prefix,
+                number,
+                files.join(", ")
+            )));
+            complete = false;
+        }
+    }
+    let max = numbers.iter().map(|(n, _)| *n).max().unwrap_or(0);
+    let gaps: Vec<String> = (1..=max)
+        .filter(|n| !numbers.iter().any(|(t, _)| t == n))
+        .map(|n| n.to_string())
+        .collect();
+    if !gaps.is_empty() {
+        findings.push(Finding::new(format!(
+            "{}Missing track numbers: {}",
+            prefix,
+            gaps.join(", ")
+        )));
+        complete = false;
+    }
+
+    let totals = count(tracks.iter().filter_map(|(_, _, t)| *t));
+    let without_total = tracks.iter().filter(|(_, _, t)| t.is_none()).count();
+    let message = match totals.as_slice() {
+        [] => return,
+        // Some tracks may not be loaded so a total that is too large isn't fixed
+        [(total, _)] if without_total == 0 => {
+            if complete && *total as usize != tracks.len() {
+                findings.push(Finding::new(format!(
+                    "{}Track total {} does not match the number of files ({})",
+                    prefix,
+                    total,
+                    tracks.len()
+                )));
+            }
+            return;
+        }
+        [(total, _)] => format!(
+            "{}No track total in {}, the others have {}",
+            prefix,
+            files(without_total),
+            total
+        ),
+        _ => format!("{}Track totals differ: {}", prefix, list_counts(&totals)),
+    };
+
+    // The total is only fixed when the tracks are complete and a file agrees
+    let total = tracks.len() as u32;
+    if !complete || !totals.iter().any(|(t, _)| *t == total) {
+        findings.push(Finding::new(message));
+        return;
+    }
+    let changes = tracks
+        .iter()
+        .filter(|(_, _, t)| *t != Some(total))
+        .map(|(e, n, _)| {
+            let frame = Frame::text("TRCK", format!("{}/{}", n, total));
+            (e.path.clone(), vec![frame])
+        })
+        .collect();
+    findings.push(Finding::with_fix(
+        message,
+        format!("Set every track total to {}", total),
+        changes,
+    ));
+}
+
+// Cover art missing from some files can be fixed if every other file has the same
+// front cover, or the same first picture if none is marked as the front cover
+fn check_cover(entries: &[&Entry], findings: &mut Vec<Finding>) {
+    let missing: Vec<&PathBuf> = entries
+        .iter()
+        .filter(|e| e.tag.pictures().next().is_none())
+        .map(|e| &e.path)
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+
+    let covers = count(entries.iter().filter_map(|e| {
+        e.tag
+            .pictures()
+            .find(|p| p.picture_type == id3::frame::PictureType::CoverFront)
+            .or_else(|| e.tag.pictures().next())
+    }));
+    let message = format!("No cover art in {}", files(missing.len()));
+    match covers.as_slice() {
+        [] => findings.push(Finding::new("No file has cover art".to_owned())),
+        [(cover, _)] => {
+            let frame = Frame::with_content("APIC", Content::Picture((*cover).clone()));
+            let changes = missing
+                .iter()
+                .map(|path| ((*path).clone(), vec![frame.clone()]))
+                .collect();
+            findings.push(Finding::with_fix(
+                message,
+                "Copy the cover art of the other files".to_owned(),
+                changes,
+            ));
+        }
+        _ => findings.push(Finding::new(format!(
+            "{}, the other files have {} different covers",
+            message,
+            covers.len()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use id3::{
+        frame::{Picture, PictureType},
+        Tag,
+    };
+
+    use super::*;
+
+    // An album file with a title, `frames` are (id, value) pairs
+    fn entry(name: &str, frames: &[(&str, &str)]) -> Entry {
+        let mut tag = Tag::new();
+        tag.set_title(name);
+        for (id, value) in frames {
+            tag.set_text(*id, *value);
+        }
+        Entry::new(PathBuf::from(format!("/album/{}", name)), tag)
+    }
+
+    fn run(entries: &[Entry]) -> Vec<Finding> {
+        lint(&entries.iter().collect::<Vec<_>>())
+    }
+
+    fn messages(entries: &[Entry]) -> Vec<String> {
+        run(entries).into_iter().map(|f| f.message).collect()
+    }
+
+    // Names of the files a fix changes and the text of their first new frame
+    fn fix(finding: &Finding) -> Vec<(String, String)> {
+        finding
+            .fix
+            .as_ref()
+            .unwrap()
+            .changes
+            .iter()
+            .map(|(path, frames)| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                let text = frames[0].content().text().unwrap_or("").to_owned();
+                (name, text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn complete_album() {
+        let entries = [
+            entry("a", &[("TALB", "X"), ("TRCK", "1/2")]),
+            entry("b", &[("TALB", "X"), ("TRCK", "2/2")]),
+        ];
+        assert_eq!(messages(&entries), ["No file has cover art"]);
+    }
+
+    #[test]
+    fn missing_album_frame_is_fixed() {
+        let entries = [
+            entry("a", &[("TALB", "X"), ("TRCK", "1")]),
+            entry("b", &[("TALB", " "), ("TRCK", "2")]),
+            entry("c", &[("TALB", "X"), ("TRCK", "3")]),
+        ];
+        let findings = run(&entries);
+        assert_eq!(findings[0].message, "Album is missing in 1 file");
+        assert_eq!(fix(&findings[0]), [("b".to_owned(), "X".to_owned())]);
+    }
+
+    #[test]
+    fn differing_album_frame_is_not_fixed() {
+        let entries = [
+            entry("a", &[("TCON", "Rock"), ("TRCK", "1")]),
+            entry("b", &[("TCON", "Pop"), ("TRCK", "2")]),
+            entry("c", &[("TCON", "Rock"), ("TRCK", "3")]),
+            entry("d", &[("TRCK", "4")]),
+        ];
+        let findings = run(&entries);
+        assert_eq!(
+            findings[0].message,
+            "Genre differs: \"Rock\" (2 files), \"Pop\" (1 file), missing in 1 file"
+        );
+        assert!(findings[0].fix.is_none());
+    }
+
+    #[test]
+    fn track_gaps_and_repeats() {
+        let entries = [
+            entry("a", &[("TRCK", "1")]),
+            entry("b", &[("TRCK", "1")]),
+            entry("c", &[("TRCK", "4")]),
+            entry("d", &[]),
+        ];
+        assert_eq!(
+            messages(&entries)[..3],
+            [
+                "d has no track number",
+                "Track 1 is used by a, b",
+                "Missing track numbers: 2, 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_track_numbers() {
+        let entries = [
+            entry("a", &[("TRCK", "0")]),
+            entry("b", &[("TRCK", "two")]),
+            entry("c", &[("TRCK", "1/x")]),
+        ];
+        assert_eq!(
+            messages(&entries)[..3],
+            [
+                "a has an invalid track number \"0\"",
+                "b has an invalid track number \"two\"",
+                "c has an invalid track number \"1/x\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_total_is_reported() {
+        let entries = [
+            entry("a", &[("TRCK", "1/3")]),
+            entry("b", &[("TRCK", "2/3")]),
+        ];
+        let findings = run(&entries);
+        assert_eq!(
+            findings[0].message,
+            "Track total 3 does not match the number of files (2)"
+        );
+        assert!(findings[0].fix.is_none());
+    }
+
+    #[test]
+    fn differing_totals_are_fixed() {
+        let entries = [
+            entry("a", &[("TRCK", "1/2")]),
+            entry("b", &[("TRCK", "2/3")]),
+            entry("c", &[("TRCK", "3")]),
+        ];
+        let findings = run(&entries);
+        assert_eq!(
+            findings[0].message,
+            "Track totals differ: \"2\" (1 file), \"3\" (1 file)"
+        );
+        assert_eq!(
+            fix(&findings[0]),
+            [
+                ("a".to_owned(), "1/3".to_owned()),
+                ("c".to_owned(), "3/3".to_owned()),
+            ]
+        );
+
+        let entries = [entry("a", &[("TRCK", "1/2")]), entry("b", &[("TRCK", "2")])];
+        let findings = run(&entries);
+        assert_eq!(
+            findings[0].message,
+            "No track total in 1 file, the others have 2"
+        );
+        assert_eq!(fix(&findings[0]), [("b".to_owned(), "2/2".to_owned())]);
+    }
+
+    #[test]
+    fn totals_are_not_fixed_with_gaps() {
+        let entries = [entry("a", &[("TRCK", "1/2")]), entry("b", &[("TRCK", "3")])];
+        let findings = run(&entries);
+        assert_eq!(
+            findings[1].message,
+            "No track total in 1 file, the others have 2"
+        );
+        assert!(findings[1].fix.is_none());
+    }
+
+    #[test]
+    fn discs_are_checked_separately() {
+        let entries = [
+            entry("a", &[("TPOS", "1/2"), ("TRCK", "1")]),
+            entry("b", &[("TPOS", "2/2"), ("TRCK", "1")]),
+            entry("c", &[("TPOS", "2"), ("TRCK", "3")]),
+            entry("d", &[("TRCK", "2")]),
+        ];
+        assert_eq!(
+            messages(&entries)[..2],
+            [
+                "Disc 2: Missing track numbers: 2",
+                "No disc: Missing track numbers: 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn cover_art_is_copied() {
+        let cover = Picture {
+            mime_type: "image/png".to_owned(),
+            picture_type: PictureType::CoverFront,
+            description: "".to_owned(),
+            data: vec![1, 2, 3],
+        };
+        let mut entries = [
+            entry("a", &[("TRCK", "1")]),
+            entry("b", &[("TRCK", "2")]),
+            entry("c", &[]),
+        ];
+        entries[0].tag.add_frame(cover.clone());
+        entries[1].tag.add_frame(cover);
+        entries[2].tag.remove_title();
+
+        let findings = run(&entries);
+        let cover = findings
+            .iter()
+            .find(|f| f.message == "No cover art in 1 file")
+            .unwrap();
+        assert_eq!(fix(cover), [("c".to_owned(), "".to_owned())]);
+        assert_eq!(findings.last().unwrap().message, "c has no title");
+    }
+}
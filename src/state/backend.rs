@@ -0,0 +1,316 @@
+use std::{
+    fs::{self, File},
+    hash::Hasher,
+    io::{self, BufWriter, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use id3::{Error, ErrorKind, Tag, Version};
+use log::warn;
+
+use crate::state::{
+    flac::Flac,
+    mpeg::{id3v2_len, trailing_tags_len, StreamInfo},
+    ogg::Ogg,
+    verify::verify_mpeg,
+};
+
+// Reads and writes the tags of one kind of audio file. Every backend converts its
+// tags to and from an `id3::Tag`, which is what the rest of the app edits, so files
+// without ID3 tags show the same frames as mp3 files.
+pub trait TagBackend: Sync {
+    fn read(&self, path: &Path) -> id3::Result<Tag>;
+    fn write(&self, tag: &Tag, path: &Path) -> id3::Result<()>;
+
+    // Whether files of this kind can also have an ID3v1 tag at the end of the file
+    fn has_id3v1(&self) -> bool {
+        false
+    }
+
+    // Audio properties of the file, `None` if they can't be read for this kind of file
+    fn stream_info(&self, _path: &Path) -> Option<StreamInfo> {
+        None
+    }
+
+    // Check the audio data of the file for corruption, returns a description of each
+    // problem found
+    fn verify_stream(&self, _path: &Path) -> io::Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    // Feed the audio data of the file to `state`, leaving out the tags so that copies
+    // of a file with different tags hash the same
+    fn hash_audio(&self, path: &Path, state: &mut dyn Hasher) -> id3::Result<()> {
+        state.write(&fs::read(path)?);
+        Ok(())
+    }
+
+    // Name of the kind of tag read from a file, shown in the files screen preview
+    fn tag_name(&self, tag: &Tag) -> String {
+        match tag.version() {
+            Version::Id3v22 => "ID3v2.2".to_owned(),
+            Version::Id3v23 => "ID3v2.3".to_owned(),
+            Version::Id3v24 => "ID3v2.4".to_owned(),
+        }
+    }
+}
+
+struct Mp3;
+
+impl TagBackend for Mp3 {
+    fn read(&self, path: &Path) -> id3::Result<Tag> {
+        Tag::read_from_path(path)
+    }
+
+    fn write(&self, tag: &Tag, path: &Path) -> id3::Result<()> {
+        tag.write_to_path(path, Version::Id3v24)
+    }
+
+    fn has_id3v1(&self) -> bool {
+        true
+    }
+
+    fn stream_info(&self, path: &Path) -> Option<StreamInfo> {
+        match StreamInfo::read(path) {
+            Ok(info) => info,
+            Err(e) => {
+                warn!("Could not read audio properties of {:?} - {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn verify_stream(&self, path: &Path) -> io::Result<Vec<String>> {
+        verify_mpeg(path)
+    }
+
+    fn hash_audio(&self, path: &Path, state: &mut dyn Hasher) -> id3::Result<()> {
+        let mut file = File::open(path)?;
+        let audio_start = id3v2_len(&mut file)?;
+        file.seek(SeekFrom::Start(audio_start))?;
+        let mut data = vec![];
+        file.read_to_end(&mut data)?;
+        state.write(&data[..data.len() - trailing_tags_len(&data)]);
+        Ok(())
+    }
+}
+
+// ID3 tag stored in an `id3 ` chunk of a RIFF WAVE file
+struct Wav;
+
+impl TagBackend for Wav {
+    fn read(&self, path: &Path) -> id3::Result<Tag> {
+        Tag::read_from_wav_path(path)
+    }
+
+    fn write(&self, tag: &Tag, path: &Path) -> id3::Result<()> {
+        tag.write_to_wav_path(path, Version::Id3v24)
+    }
+
+    fn hash_audio(&self, path: &Path, state: &mut dyn Hasher) -> id3::Result<()> {
+        hash_chunk(path, b"data", false, state)
+    }
+}
+
+// ID3 tag stored in an `ID3 ` chunk of an AIFF file
+struct Aiff;
+
+impl TagBackend for Aiff {
+    fn read(&self, path: &Path) -> id3::Result<Tag> {
+        Tag::read_from_aiff_path(path)
+    }
+
+    fn write(&self, tag: &Tag, path: &Path) -> id3::Result<()> {
+        tag.write_to_aiff_path(path, Version::Id3v24)
+    }
+
+    fn hash_audio(&self, path: &Path, state: &mut dyn Hasher) -> id3::Result<()> {
+        hash_chunk(path, b"SSND", true, state)
+    }
+}
+
+// Feed the contents of the first `id` chunk of a RIFF or AIFF file to `state`, the
+// sizes of AIFF chunks are big endian
+fn hash_chunk(
+    path: &Path,
+    id: &[u8; 4],
+    big_endian: bool,
+    state: &mut dyn Hasher,
+) -> id3::Result<()> {
+    let data = fs::read(path)?;
+    // Chunks start after the 12 byte RIFF or FORM header
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let size = data[pos + 4..pos + 8].try_into().unwrap();
+        let len = match big_endian {
+            true => u32::from_be_bytes(size),
+            false => u32::from_le_bytes(size),
+        } as usize;
+        let start = pos + 8;
+        if &data[pos..pos + 4] == id {
+            state.write(&data[start..(start + len).min(data.len())]);
+            return Ok(());
+        }
+        // Chunks are padded to an even length
+        pos = start + len + len % 2;
+    }
+    Err(Error::new(
+        ErrorKind::Parsing,
+        format!("No {} chunk found", String::from_utf8_lossy(id)),
+    ))
+}
+
+// Get the backend for a file from its extension, ignoring case. Returns `None` for
+// files that can't be tagged.
+pub fn backend_for(path: &Path) -> Option<&'static dyn TagBackend> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "mp3" => Some(&Mp3),
+        "wav" => Some(&Wav),
+        "aif" | "aiff" => Some(&Aiff),
+        "flac" => Some(&Flac),
+        "ogg" | "oga" | "opus" => Some(&Ogg),
+        _ => None,
+    }
+}
+
+// Write a new version of the file at `path` with `write` and move it over the old one.
+// The new file is written next to the old one so a failed write never leaves a
+// broken file behind. It gets the permissions of the old file, but it is owned by the
+// user running the app and other hard links to the old file keep the old contents.
+pub fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> id3::Result<()>,
+) -> id3::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.rid3", file_name));
+
+    let permissions = fs::metadata(path)?.permissions();
+    let result = (|| {
+        let mut out = BufWriter::new(File::create(&temp_path)?);
+        write(&mut out)?;
+        let file = out.into_inner().map_err(|e| e.into_error())?;
+        file.set_permissions(permissions)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::hash_map::DefaultHasher, io::Write};
+
+    use id3::TagLike;
+
+    use super::*;
+    use crate::state::test_dir::TestDir;
+
+    fn audio_hash(path: &Path) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        backend_for(path)
+            .unwrap()
+            .hash_audio(path, &mut hasher)
+            .unwrap();
+        hasher.finish()
+    }
+
+    // A RIFF WAVE file with a `fmt ` chunk and an odd length `data` chunk
+    fn wav_bytes() -> Vec<u8> {
+        let mut chunks = b"WAVEfmt ".to_vec();
+        chunks.extend_from_slice(&16u32.to_le_bytes());
+        chunks.extend_from_slice(&[1, 0, 1, 0, 0x44, 0xac, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0]);
+        chunks.extend_from_slice(b"data");
+        chunks.extend_from_slice(&5u32.to_le_bytes());
+        chunks.extend_from_slice(&[1, 2, 3, 4, 5, 0]);
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+        bytes.extend(chunks);
+        bytes
+    }
+
+    #[test]
+    fn backend_by_extension() {
+        assert!(backend_for(Path::new("a.MP3")).is_some());
+        assert!(backend_for(Path::new("a.wav")).is_some());
+        assert!(backend_for(Path::new("a.aif")).is_some());
+        assert!(backend_for(Path::new("a.Flac")).is_some());
+        assert!(backend_for(Path::new("a.opus")).is_some());
+        assert!(backend_for(Path::new("a.m4a")).is_none());
+        assert!(backend_for(Path::new("mp3")).is_none());
+    }
+
+    #[test]
+    fn wav_round_trip() {
+        let dir = TestDir::new("wav");
+        let path = dir.file("a.wav", &wav_bytes());
+        let hash = audio_hash(&path);
+
+        let backend = backend_for(&path).unwrap();
+        let mut tag = Tag::new();
+        tag.set_title("Song");
+        backend.write(&tag, &path).unwrap();
+        assert_eq!(backend.read(&path).unwrap().title(), Some("Song"));
+        let data = fs::read(&path).unwrap();
+        assert!(data
+            .windows(13)
+            .any(|w| w == b"data\x05\0\0\0\x01\x02\x03\x04\x05"));
+        assert_eq!(audio_hash(&path), hash);
+    }
+
+    #[test]
+    fn mp3_hash_ignores_tags() {
+        let dir = TestDir::new("mp3-hash");
+        let mut data = vec![0xff, 0xfb, 0x90, 0x64];
+        data.resize(417, 0);
+        let path = dir.file("a.mp3", &data);
+        let hash = audio_hash(&path);
+
+        let mut tag = Tag::new();
+        tag.set_title("Song");
+        tag.write_to_path(&path, Version::Id3v24).unwrap();
+        let mut v1 = b"TAG".to_vec();
+        v1.resize(128, 0);
+        File::options()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&v1)
+            .unwrap();
+        assert_eq!(audio_hash(&path), hash);
+    }
+
+    #[test]
+    fn replace_file_failure_keeps_original() {
+        let dir = TestDir::new("replace");
+        let path = dir.file("a.bin", b"original");
+        let result = replace_file(&path, |out| {
+            out.write_all(b"partial")?;
+            Err(Error::new(ErrorKind::InvalidInput, "failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        assert!(!path.with_file_name(".a.bin.rid3").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replace_file_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("replace");
+        let path = dir.file("a.bin", b"original");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o604)).unwrap();
+        replace_file(&path, |out| Ok(out.write_all(b"new")?)).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o604
+        );
+    }
+}
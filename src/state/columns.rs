@@ -0,0 +1,40 @@
+use id3::{Content, TagLike};
+
+use crate::state::{frame_data, main_state::Entry};
+
+// A column of the main screen table view
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    FileName,
+    Frame(String),
+}
+
+impl Column {
+    // Columns are configured by name (`artist`) or frame id (`TPE1`)
+    pub fn parse(text: &str) -> Self {
+        match text.to_lowercase().as_str() {
+            "filename" => Column::FileName,
+            name => {
+                Column::Frame(frame_data::name_to_id(name).unwrap_or_else(|_| text.to_uppercase()))
+            }
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            Column::FileName => "Filename".to_owned(),
+            Column::Frame(id) => frame_data::id_to_name(id).unwrap_or_else(|_| id.clone()),
+        }
+    }
+
+    pub fn value(&self, entry: &Entry) -> String {
+        match self {
+            Column::FileName => entry.filename.clone(),
+            Column::Frame(id) => match entry.tag.get(id).map(|f| f.content()) {
+                Some(Content::Text(text)) => text.clone(),
+                Some(content) => content.to_string(),
+                None => "".to_owned(),
+            },
+        }
+    }
+}
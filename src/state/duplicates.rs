@@ -0,0 +1,181 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::Hasher,
+    path::{Path, PathBuf},
+};
+
+use id3::{Error, ErrorKind, TagLike};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::state::{backend::backend_for, main_state::Entry};
+
+// What two files must have in common to be reported as copies of each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateMode {
+    Tags,
+    Audio,
+    Both,
+}
+
+impl DuplicateMode {
+    pub const ALL: [DuplicateMode; 3] = [
+        DuplicateMode::Tags,
+        DuplicateMode::Audio,
+        DuplicateMode::Both,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DuplicateMode::Tags => "Artist, title and duration",
+            DuplicateMode::Audio => "Audio data",
+            DuplicateMode::Both => "Tags and audio data",
+        }
+    }
+
+    // Whether the audio of every file has to be hashed first
+    pub fn uses_audio(&self) -> bool {
+        *self != DuplicateMode::Tags
+    }
+
+    // The value that is equal for copies of a file, `None` if the file can't be
+    // compared. `hashes` holds the audio hash of each file.
+    pub fn key(&self, entry: &Entry, hashes: &HashMap<PathBuf, u64>) -> Option<String> {
+        let hash = || hashes.get(&entry.path).map(|h| format!("{:016x}", h));
+        match self {
+            DuplicateMode::Tags => tag_key(entry),
+            DuplicateMode::Audio => hash(),
+            DuplicateMode::Both => Some(format!("{}\0{}", tag_key(entry)?, hash()?)),
+        }
+    }
+}
+
+// Artist, title and duration of a file, compared ignoring case, unicode form and
+// repeated spaces. Durations are rounded to whole seconds as different encodes of a
+// track vary slightly, and files without a duration only match each other.
+fn tag_key(entry: &Entry) -> Option<String> {
+    let artist = normalise(entry.tag.artist()?);
+    let title = normalise(entry.tag.title()?);
+    if artist.is_empty() || title.is_empty() {
+        return None;
+    }
+
+    let seconds = match &entry.info {
+        Some(info) => Some(info.duration.as_secs_f64()),
+        None => entry.tag.duration().map(|ms| ms as f64 / 1000.0),
+    };
+    let duration = seconds
+        .map(|s| (s.round() as u64).to_string())
+        .unwrap_or_default();
+    Some(format!("{}\0{}\0{}", artist, title, duration))
+}
+
+fn normalise(text: &str) -> String {
+    let text = text.nfkc().collect::<String>().to_lowercase();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Hash of the audio data of a file, files that only differ in their tags have the
+// same hash
+pub fn audio_hash(path: &Path) -> id3::Result<u64> {
+    let backend = backend_for(path)
+        .ok_or_else(|| Error::new(ErrorKind::UnsupportedFeature, "Unsupported file type"))?;
+    let mut state = DefaultHasher::new();
+    backend.hash_audio(path, &mut state)?;
+    Ok(state.finish())
+}
+
+// Group the paths of the entries with equal keys. Groups are in the order of their
+// first entry and entries keep their order within a group, groups of one are left out.
+pub fn group<'a>(entries: impl Iterator<Item = (Option<String>, &'a Entry)>) -> Vec<Vec<PathBuf>> {
+    let mut groups: Vec<Vec<PathBuf>> = vec![];
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (key, entry) in entries {
+        let key = match key {
+            Some(key) => key,
+            None => continue,
+        };
+        match index.get(&key) {
+            Some(i) => groups[*i].push(entry.path.clone()),
+            None => {
+                index.insert(key, groups.len());
+                groups.push(vec![entry.path.clone()]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use id3::Tag;
+
+    use super::*;
+
+    fn entry(path: &str, artist: &str, title: &str, duration: Option<u32>) -> Entry {
+        let mut tag = Tag::new();
+        tag.set_artist(artist);
+        tag.set_title(title);
+        if let Some(ms) = duration {
+            tag.set_duration(ms);
+        }
+        Entry::new(PathBuf::from(path), tag)
+    }
+
+    fn groups(
+        mode: DuplicateMode,
+        entries: &[Entry],
+        hashes: &HashMap<PathBuf, u64>,
+    ) -> Vec<Vec<String>> {
+        let keyed = entries.iter().map(|e| (mode.key(e, hashes), e));
+        group(keyed)
+            .into_iter()
+            .map(|g| g.iter().map(|p| p.to_string_lossy().into_owned()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn tag_keys_are_normalised() {
+        let hashes = HashMap::new();
+        let key = |e: &Entry| DuplicateMode::Tags.key(e, &hashes);
+        let a = entry("/a.mp3", "The  Band", "Ｓｏｎｇ", Some(200_400));
+        let b = entry("/b.mp3", " the band ", "song", Some(199_600));
+        assert_eq!(key(&a), key(&b));
+        assert_eq!(key(&a).unwrap(), ["the band", "song", "200"].join("\0"));
+
+        // Durations a second apart and missing durations don't match
+        assert_ne!(
+            key(&a),
+            key(&entry("/c.mp3", "The Band", "Song", Some(201_600)))
+        );
+        assert_ne!(key(&a), key(&entry("/d.mp3", "The Band", "Song", None)));
+        assert_eq!(key(&entry("/e.mp3", "", "Song", None)), None);
+        assert_eq!(key(&Entry::new(PathBuf::from("/f.mp3"), Tag::new())), None);
+    }
+
+    #[test]
+    fn group_by_mode() {
+        let entries = [
+            entry("/a.mp3", "A", "One", None),
+            entry("/b.mp3", "B", "Two", None),
+            entry("/c.mp3", "a", "one", None),
+            entry("/d.mp3", "B", "two", None),
+            entry("/e.mp3", "C", "Three", None),
+        ];
+        let hashes: HashMap<PathBuf, u64> =
+            [("/a.mp3", 1), ("/b.mp3", 1), ("/c.mp3", 2), ("/e.mp3", 1)]
+                .into_iter()
+                .map(|(p, h)| (PathBuf::from(p), h))
+                .collect();
+
+        assert_eq!(
+            groups(DuplicateMode::Tags, &entries, &hashes),
+            [vec!["/a.mp3", "/c.mp3"], vec!["/b.mp3", "/d.mp3"]]
+        );
+        assert_eq!(
+            groups(DuplicateMode::Audio, &entries, &hashes),
+            [vec!["/a.mp3", "/b.mp3", "/e.mp3"]]
+        );
+        assert!(groups(DuplicateMode::Both, &entries, &hashes).is_empty());
+    }
+}
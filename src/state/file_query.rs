@@ -0,0 +1,196 @@
+use id3::{frame::ExtendedText, Content};
+
+use crate::state::{frame_data, main_state::Entry};
+
+// What a single search term is matched against
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum QueryField {
+    // The filename or any text frame
+    Any,
+    FileName,
+    Frame(String),
+}
+
+// A search over the loaded files such as `artist:foo album:"bar baz" live`. Terms
+// without a field match the filename or any text frame, every term must match and
+// matching ignores case.
+#[derive(Debug, Clone)]
+pub struct FileQuery {
+    terms: Vec<(QueryField, String)>,
+}
+
+impl FileQuery {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut terms = vec![];
+        let mut chars = text.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+
+            // A word is either the field name or the whole value of the term
+            let mut word = String::new();
+            if chars.next_if_eq(&'"').is_some() {
+                word = read_quoted(&mut chars)?;
+                terms.push((QueryField::Any, word.to_lowercase()));
+                continue;
+            }
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ':') {
+                word.push(c);
+            }
+
+            if chars.next_if_eq(&':').is_none() {
+                terms.push((QueryField::Any, word.to_lowercase()));
+                continue;
+            }
+
+            let field = parse_field(&word)?;
+            let value = match chars.next_if_eq(&'"') {
+                Some(_) => read_quoted(&mut chars)?,
+                None => {
+                    let mut value = String::new();
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        value.push(c);
+                    }
+                    value
+                }
+            };
+            terms.push((field, value.to_lowercase()));
+        }
+
+        Ok(Self { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&self, entry: &Entry) -> bool {
+        self.terms.iter().all(|(field, value)| match field {
+            QueryField::Any => {
+                contains(&entry.filename, value)
+                    || entry
+                        .tag
+                        .frames()
+                        .any(|f| frame_text(f.content()).is_some_and(|t| contains(t, value)))
+            }
+            QueryField::FileName => contains(&entry.filename, value),
+            QueryField::Frame(id) => entry
+                .tag
+                .frames()
+                .filter(|f| f.id() == id)
+                .any(|f| frame_text(f.content()).is_some_and(|t| contains(t, value))),
+        })
+    }
+}
+
+// Read up to and including the closing quote, the opening quote has already been read
+fn read_quoted(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut value = String::new();
+    for c in chars {
+        if c == '"' {
+            return Ok(value);
+        }
+        value.push(c);
+    }
+    Err("Missing closing quote in search".to_owned())
+}
+
+// Fields are given by name (`artist`), frame id (`TPE1`) or `filename`
+fn parse_field(name: &str) -> Result<QueryField, String> {
+    let lower = name.to_lowercase();
+    if lower == "filename" {
+        return Ok(QueryField::FileName);
+    }
+    if let Ok(id) = frame_data::name_to_id(&lower) {
+        return Ok(QueryField::Frame(id));
+    }
+    // Any other frame can be searched by its id
+    let is_id = name.len() == 4
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+    match is_id {
+        true => Ok(QueryField::Frame(name.to_owned())),
+        false => Err(format!("Unknown field in search: '{}'", name)),
+    }
+}
+
+fn frame_text(content: &Content) -> Option<&str> {
+    match content {
+        Content::Text(text) => Some(text),
+        Content::ExtendedText(ExtendedText { value, .. }) => Some(value),
+        _ => None,
+    }
+}
+
+// `value` is already lower case
+fn contains(text: &str, value: &str) -> bool {
+    text.to_lowercase().contains(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use id3::{Tag, TagLike};
+
+    use super::*;
+
+    fn entry() -> Entry {
+        let mut tag = Tag::new();
+        tag.set_artist("Some Artist");
+        tag.set_album("Live At The Hall");
+        tag.set_text("TCOM", "Composer");
+        tag.add_frame(ExtendedText {
+            description: "MOOD".to_owned(),
+            value: "Calm".to_owned(),
+        });
+        Entry::new(PathBuf::from("/music/01 Song.mp3"), tag)
+    }
+
+    fn matches(query: &str) -> bool {
+        FileQuery::parse(query).unwrap().matches(&entry())
+    }
+
+    #[test]
+    fn terms_match_any_field() {
+        assert!(matches("song"));
+        assert!(matches("ARTIST"));
+        assert!(matches("calm"));
+        assert!(!matches("missing"));
+        // Every term has to match
+        assert!(matches("song live"));
+        assert!(!matches("song missing"));
+    }
+
+    #[test]
+    fn fields() {
+        assert!(matches("artist:some"));
+        assert!(!matches("album:some"));
+        assert!(matches("filename:01"));
+        assert!(!matches("filename:artist"));
+        assert!(matches("TCOM:comp"));
+        assert!(matches("TXXX:calm"));
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert!(matches("album:\"at the hall\""));
+        assert!(!matches("album:\"the at\""));
+        assert!(matches("\"some artist\""));
+        assert!(matches("album:\"\""));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(FileQuery::parse("album:\"live").is_err());
+        assert!(FileQuery::parse("\"live").is_err());
+        assert!(FileQuery::parse("colour:red").is_err());
+        // Lower case ids are not frame ids
+        assert!(FileQuery::parse("tcom:x").is_err());
+        assert!(FileQuery::parse("  ").unwrap().is_empty());
+    }
+}
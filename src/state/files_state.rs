@@ -1,18 +1,36 @@
 use std::{
     cmp::Ordering,
+    collections::{BTreeSet, HashMap},
     fs::{self, DirEntry},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
-use crossterm::event::KeyEvent;
-use id3::Tag;
+use crossterm::event::{KeyCode, KeyEvent};
+use id3::{Frame, Tag, TagLike};
 use log::{error, warn};
+use regex::Regex;
+use tokio::sync::mpsc::UnboundedSender;
 use tui::widgets::ListState;
 
 use crate::{
     configuration::{actions::Action, Config},
-    popups::{help::HelpPopup, Popup},
-    state::{main_state::Entry, update_screen_state, AppEvent, ScreenState},
+    popups::{
+        form::{FormField, FormKind, FormPopup},
+        help::HelpPopup,
+        progress::ProgressPopup,
+        table::{TableKind, TablePopup},
+        Popup, PopupData,
+    },
+    state::{
+        backend::backend_for,
+        fuzzy::Finder,
+        jobs::{spawn_add_recursive, spawn_preview, JobEvent, Progress},
+        main_state::Entry,
+        tag_pattern::{TagPattern, TOKEN_REGEX},
+        tag_preview::TagPreview,
+        update_screen_state, AppEvent, ScreenState,
+    },
     util, LOGGER,
 };
 
@@ -21,6 +39,15 @@ pub enum FilesStateItem {
     DirEntry(DirEntry),
 }
 
+impl FilesStateItem {
+    pub fn name(&self) -> String {
+        match self {
+            FilesStateItem::Parent => "../".to_owned(),
+            FilesStateItem::DirEntry(entry) => entry.file_name().to_string_lossy().into_owned(),
+        }
+    }
+}
+
 pub struct FilesState {
     pub popup_stack: Vec<Box<dyn Popup>>,
 
@@ -31,12 +58,36 @@ pub struct FilesState {
     pub show_hidden_dirs: bool,
 
     help_text: Vec<String>,
+    rex: Regex,
+    path_pattern: String,
+    // Files found by the tags from path preview with the frames parsed from their paths
+    pending_files: Vec<(PathBuf, Vec<Frame>)>,
+
+    // Fuzzy finder over `files`, `None` when the full listing is shown
+    pub finder: Option<Finder>,
+
+    job_tx: UnboundedSender<JobEvent>,
+    // Progress of the running recursive add
+    job: Option<Arc<Progress>>,
+
+    // Paths of the files and directories marked to be added, marks are kept when
+    // changing directory
+    marked: BTreeSet<PathBuf>,
+    // Index of the last item marked in the current directory, the start of a range
+    mark_anchor: Option<usize>,
+
+    pub show_preview: bool,
+    // Previews of the files highlighted in the current directory, `None` while the
+    // preview is being read
+    previews: HashMap<PathBuf, Option<TagPreview>>,
+    // How many directories below the current one are searched by a recursive add
+    max_depth: Option<usize>,
 
     trigger_logs: bool,
 }
 
 impl FilesState {
-    pub fn new(dir: PathBuf) -> Result<Self, anyhow::Error> {
+    pub fn new(dir: PathBuf, job_tx: UnboundedSender<JobEvent>) -> Result<Self, anyhow::Error> {
         let mut files: Vec<FilesStateItem> = get_entries(&dir, false)?;
         sort_files(&mut files);
 
@@ -49,6 +100,17 @@ impl FilesState {
             files_state: ListState::default(),
             show_hidden_dirs: false,
             help_text: vec![],
+            rex: Regex::new(TOKEN_REGEX).unwrap(),
+            path_pattern: "{artist}/{album}/{track} {title}.mp3".to_owned(),
+            pending_files: vec![],
+            finder: None,
+            job_tx,
+            job: None,
+            marked: BTreeSet::new(),
+            mark_anchor: None,
+            show_preview: true,
+            previews: HashMap::new(),
+            max_depth: None,
             trigger_logs: false,
         })
     }
@@ -56,7 +118,7 @@ impl FilesState {
     pub fn handle_input(
         &mut self,
         key: &KeyEvent,
-        actions: &Vec<Action>,
+        actions: &[Action],
         show_logs: &mut bool,
     ) -> AppEvent {
         let action = if actions.len() == 1 {
@@ -69,6 +131,12 @@ impl FilesState {
                     || *a == Action::ParentDir
                     || *a == Action::EnterDir
                     || *a == Action::HiddenDir
+                    || *a == Action::TagsFromPath
+                    || *a == Action::FuzzyFind
+                    || *a == Action::AddRecursive
+                    || *a == Action::ToggleMark
+                    || *a == Action::MarkRange
+                    || *a == Action::TogglePreview
                 {
                     action = *a;
                     break;
@@ -78,12 +146,40 @@ impl FilesState {
         };
         self.trigger_logs = false;
 
+        if self.popup_stack.is_empty() && self.finder.is_some() {
+            let event = self.handle_finder_input(key, action);
+            self.request_preview();
+            if self.trigger_logs {
+                *show_logs = true;
+            }
+            return event;
+        }
+
         if let Some(popup) = self.popup_stack.last_mut() {
             match popup.handle_input(key, action) {
                 AppEvent::ClosePopup => {
-                    // Need to return relevant data from popup here, probably another enum...
                     let _p = self.popup_stack.pop().unwrap();
                 }
+                AppEvent::ClosePopupData(data) => {
+                    let _p = self.popup_stack.pop().unwrap();
+                    match data {
+                        PopupData::Form(FormKind::TagsFromPath, values) => {
+                            if let Err(e) = self.preview_tags_from_path(values[0].text().to_owned())
+                            {
+                                warn!("{}", e);
+                                self.trigger_logs = true;
+                            }
+                        }
+                        PopupData::Table(TableKind::Preview, _) => {
+                            let event = self.add_pending_files();
+                            if self.trigger_logs {
+                                *show_logs = true;
+                            }
+                            return event;
+                        }
+                        _ => {}
+                    }
+                }
                 AppEvent::SwitchScreen(s) => return update_screen_state(s),
                 _ => {}
             }
@@ -104,6 +200,10 @@ impl FilesState {
                 Action::Next => self.next(),
                 Action::AddAllFiles => return self.add_all_files().expect("Could not add files"),
                 Action::AddFile => return self.add_file().expect("Could not add file"),
+                Action::AddRecursive => self.add_recursive(),
+                Action::ToggleMark => self.toggle_mark(),
+                Action::MarkRange => self.mark_range(),
+                Action::TogglePreview => self.show_preview = !self.show_preview,
                 Action::ParentDir => self.parent_dir().expect("Could not enter parent directory"),
                 Action::EnterDir => {
                     if let Some(i) = self.files_state.selected() {
@@ -117,6 +217,8 @@ impl FilesState {
                         }
                     }
                 }
+                Action::TagsFromPath => self.spawn_tags_from_path_popup(),
+                Action::FuzzyFind => self.finder = Some(Finder::new(&self.item_names())),
                 Action::HiddenDir => {
                     self.show_hidden_dirs = !self.show_hidden_dirs;
                     if let Err(e) = self.refresh_dir() {
@@ -128,12 +230,47 @@ impl FilesState {
             }
         }
 
+        self.request_preview();
         if self.trigger_logs {
             *show_logs = true;
         }
         AppEvent::None
     }
 
+    // Path of the file highlighted in the listing or the fuzzy finder
+    fn highlighted_file(&self) -> Option<PathBuf> {
+        let index = match &self.finder {
+            Some(finder) => finder.selected(),
+            None => self.files_state.selected(),
+        }?;
+        self.item_path(index).filter(|path| !path.is_dir())
+    }
+
+    // Start reading the preview of the highlighted file unless it is already cached
+    fn request_preview(&mut self) {
+        if !self.show_preview {
+            return;
+        }
+        if let Some(path) = self.highlighted_file() {
+            if !self.previews.contains_key(&path) {
+                self.previews.insert(path.clone(), None);
+                spawn_preview(path, self.job_tx.clone());
+            }
+        }
+    }
+
+    pub fn set_preview(&mut self, path: PathBuf, preview: TagPreview) {
+        self.previews.insert(path, Some(preview));
+    }
+
+    // Preview of the highlighted file, the outer `None` when a directory is
+    // highlighted and the inner `None` while the preview is loading
+    pub fn preview(&self) -> Option<Option<&TagPreview>> {
+        self.highlighted_file()
+            .and_then(|path| self.previews.get(&path))
+            .map(|preview| preview.as_ref())
+    }
+
     pub fn next(&mut self) {
         let i = match self.files_state.selected() {
             Some(i) => util::next(i, self.files.len()),
@@ -150,24 +287,151 @@ impl FilesState {
         self.files_state.select(Some(i));
     }
 
-    fn enter_dir(&mut self, index: usize) -> Result<(), anyhow::Error> {
-        match &self.files[index] {
-            FilesStateItem::DirEntry(entry) => {
-                if entry.file_type()?.is_dir() {
-                    let path = entry.path();
-                    let files = get_entries(&path, self.show_hidden_dirs)?;
-
-                    self.current_dir = path;
-                    self.files = files;
-                    sort_files(&mut self.files);
-                    self.files_state = ListState::default();
-                    self.files_state.select(Some(0));
-                } else {
-                    warn!("Not a directory!");
-                    self.trigger_logs = true;
+    fn item_names(&self) -> Vec<String> {
+        self.files.iter().map(|item| item.name()).collect()
+    }
+
+    // Keys typed while the fuzzy finder is open. `Enter` enters the highlighted
+    // directory or adds the highlighted file and `Esc` shows the full listing again,
+    // both with the same item highlighted.
+    fn handle_finder_input(&mut self, key: &KeyEvent, action: Action) -> AppEvent {
+        let finder = match &mut self.finder {
+            Some(finder) => finder,
+            None => return AppEvent::None,
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                if let Some(i) = finder.selected() {
+                    self.files_state.select(Some(i));
+                }
+                self.finder = None;
+            }
+            KeyCode::Enter => {
+                let index = finder.selected();
+                self.finder = None;
+                if let Some(i) = index {
+                    self.files_state.select(Some(i));
+                    return self.open_item(i);
+                }
+            }
+            KeyCode::Backspace => {
+                finder.query.pop();
+                let names = self.item_names();
+                if let Some(finder) = &mut self.finder {
+                    finder.update(&names);
                 }
             }
-            _ => {}
+            KeyCode::Char(c) => {
+                finder.query.push(c);
+                let names = self.item_names();
+                if let Some(finder) = &mut self.finder {
+                    finder.update(&names);
+                }
+            }
+            _ => match action {
+                Action::Prev if !finder.matches.is_empty() => {
+                    let i = finder.list_state.selected().unwrap_or(0);
+                    let i = util::prev(i, finder.matches.len());
+                    finder.list_state.select(Some(i));
+                }
+                Action::Next if !finder.matches.is_empty() => {
+                    let i = finder.list_state.selected().unwrap_or(0);
+                    let i = util::next(i, finder.matches.len());
+                    finder.list_state.select(Some(i));
+                }
+                _ => {}
+            },
+        }
+        AppEvent::None
+    }
+
+    // Enter a directory or add a file picked in the fuzzy finder
+    fn open_item(&mut self, index: usize) -> AppEvent {
+        let result = match &self.files[index] {
+            FilesStateItem::Parent => self.parent_dir().map(|_| AppEvent::None),
+            FilesStateItem::DirEntry(entry) if entry.path().is_dir() => {
+                self.enter_dir(index).map(|_| AppEvent::None)
+            }
+            FilesStateItem::DirEntry(_) => self.add_highlighted(),
+        };
+
+        result.unwrap_or_else(|e| {
+            warn!("{}", e);
+            self.trigger_logs = true;
+            AppEvent::None
+        })
+    }
+
+    fn item_path(&self, index: usize) -> Option<PathBuf> {
+        match self.files.get(index) {
+            Some(FilesStateItem::DirEntry(entry)) => Some(entry.path()),
+            _ => None,
+        }
+    }
+
+    pub fn is_marked(&self, item: &FilesStateItem) -> bool {
+        match item {
+            FilesStateItem::DirEntry(entry) => self.marked.contains(&entry.path()),
+            FilesStateItem::Parent => false,
+        }
+    }
+
+    pub fn marked_count(&self) -> usize {
+        self.marked.len()
+    }
+
+    // Mark or unmark the highlighted item
+    fn toggle_mark(&mut self) {
+        let index = match self.files_state.selected() {
+            Some(i) => i,
+            None => return,
+        };
+        if let Some(path) = self.item_path(index) {
+            if !self.marked.remove(&path) {
+                self.marked.insert(path);
+            }
+            self.mark_anchor = Some(index);
+        }
+        self.next();
+    }
+
+    // Mark every item between the last toggled item and the highlighted item
+    fn mark_range(&mut self) {
+        let index = match self.files_state.selected() {
+            Some(i) => i,
+            None => return,
+        };
+        let anchor = match self.mark_anchor {
+            Some(anchor) => anchor,
+            None => return self.toggle_mark(),
+        };
+
+        for i in anchor.min(index)..=anchor.max(index) {
+            if let Some(path) = self.item_path(i) {
+                self.marked.insert(path);
+            }
+        }
+        self.mark_anchor = Some(index);
+    }
+
+    fn enter_dir(&mut self, index: usize) -> Result<(), anyhow::Error> {
+        if let FilesStateItem::DirEntry(entry) = &self.files[index] {
+            if entry.file_type()?.is_dir() {
+                let path = entry.path();
+                let files = get_entries(&path, self.show_hidden_dirs)?;
+
+                self.current_dir = path;
+                self.files = files;
+                sort_files(&mut self.files);
+                self.files_state = ListState::default();
+                self.files_state.select(Some(0));
+                self.mark_anchor = None;
+                self.previews.clear();
+            } else {
+                warn!("Not a directory!");
+                self.trigger_logs = true;
+            }
         }
 
         Ok(())
@@ -183,6 +447,8 @@ impl FilesState {
                 sort_files(&mut self.files);
                 self.files_state = ListState::default();
                 self.files_state.select(Some(0));
+                self.mark_anchor = None;
+                self.previews.clear();
             }
             None => { /* Must be at root */ }
         }
@@ -197,28 +463,164 @@ impl FilesState {
         sort_files(&mut self.files);
         self.files_state = ListState::default();
         self.files_state.select(Some(0));
+        self.mark_anchor = None;
+        self.previews.clear();
         Ok(())
     }
 
-    // Append selected file to MainState files
+    // Append the marked files to MainState files, or the highlighted file if nothing
+    // is marked
     fn add_file(&mut self) -> Result<AppEvent, anyhow::Error> {
+        if self.marked.is_empty() {
+            return self.add_highlighted();
+        }
+
+        let marked: Vec<PathBuf> = std::mem::take(&mut self.marked).into_iter().collect();
+        if marked.iter().any(|path| path.is_dir()) {
+            self.spawn_add_job(marked);
+            return Ok(AppEvent::None);
+        }
+        Ok(AppEvent::AddFiles(
+            marked.into_iter().map(Entry::loading).collect(),
+        ))
+    }
+
+    // Append highlighted file to MainState files, the tag is read in the background
+    fn add_highlighted(&mut self) -> Result<AppEvent, anyhow::Error> {
         match self.files_state.selected().unwrap() {
             0 => Ok(AppEvent::None),
-            i => {
-                let tag = get_tags(&self.files[i..i + 1])?;
-                Ok(AppEvent::AddFiles(tag))
-            }
+            i => Ok(AppEvent::AddFiles(loading_entries(&self.files[i..i + 1]))),
         }
     }
 
     // Append all files to MainState files
     fn add_all_files(&mut self) -> Result<AppEvent, anyhow::Error> {
-        let tags = get_tags(&self.files[1..])?;
-        Ok(AppEvent::AddFiles(tags))
+        Ok(AppEvent::AddFiles(loading_entries(&self.files[1..])))
+    }
+
+    // Add every file below the current directory, the tags are read in the background
+    // and the files appear on the main screen as they are read
+    fn add_recursive(&mut self) {
+        self.spawn_add_job(vec![self.current_dir.clone()]);
+    }
+
+    // Add `paths` in the background, directories are searched recursively
+    fn spawn_add_job(&mut self, paths: Vec<PathBuf>) {
+        if self.job.is_some() {
+            warn!("Files are still being added");
+            self.trigger_logs = true;
+            return;
+        }
+
+        let progress = Arc::new(Progress::default());
+        spawn_add_recursive(
+            paths,
+            self.show_hidden_dirs,
+            self.max_depth,
+            progress.clone(),
+            self.job_tx.clone(),
+        );
+        self.popup_stack.push(Box::new(ProgressPopup::new(
+            "Adding Files",
+            progress.clone(),
+        )));
+        self.job = Some(progress);
+    }
+
+    // Called when the background job has ended, the progress popup is removed unless
+    // it was already closed by cancelling the job
+    pub fn finish_job(&mut self) {
+        if let Some(progress) = self.job.take() {
+            if !progress.is_cancelled() {
+                self.popup_stack.pop();
+            }
+        }
+    }
+
+    pub fn apply_config(&mut self, config: &Config) {
+        self.max_depth = config.get_recursive_depth();
+    }
+
+    fn spawn_tags_from_path_popup(&mut self) {
+        let popup = FormPopup::new(
+            "Tags From Path",
+            FormKind::TagsFromPath,
+            vec![FormField::text("Pattern", &self.path_pattern)],
+        );
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Find every file below the current directory that matches the pattern and show
+    // the frames parsed from their paths in a preview popup. The files are added with
+    // the new frames when the preview is confirmed.
+    fn preview_tags_from_path(&mut self, pattern: String) -> Result<(), anyhow::Error> {
+        let tag_pattern = match TagPattern::new(&pattern, &self.rex) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("{}", e);
+                self.trigger_logs = true;
+                return Ok(());
+            }
+        };
+        self.path_pattern = pattern;
+
+        let mut rows = vec![];
+        self.pending_files.clear();
+        for path in walk_files(
+            &self.current_dir,
+            self.show_hidden_dirs,
+            tag_pattern.depth(),
+        )? {
+            let filename = match path.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            let subject = match tag_pattern.subject(Some(&self.current_dir), &path, &filename) {
+                Some(s) => s,
+                None => continue,
+            };
+            if let Some(values) = tag_pattern.captures(&subject) {
+                let frames = tag_pattern.frames(&values);
+                self.pending_files.push((path, frames));
+
+                let mut row = vec![subject];
+                row.extend(values);
+                rows.push(row);
+            }
+        }
+
+        if rows.is_empty() {
+            warn!("No files match pattern '{}'", self.path_pattern);
+            self.trigger_logs = true;
+            return Ok(());
+        }
+
+        let popup = TablePopup::new(
+            "Tags From Path",
+            TableKind::Preview,
+            tag_pattern.preview_header(),
+            rows,
+        );
+        self.popup_stack.push(Box::new(popup));
+        Ok(())
     }
 
-    pub fn popup_widget(&self) -> Option<&Box<dyn Popup>> {
-        self.popup_stack.last()
+    // Add the files confirmed in the tags from path preview with the parsed frames,
+    // the frames are kept when the rest of the tag is loaded
+    fn add_pending_files(&mut self) -> AppEvent {
+        let mut entries = vec![];
+        for (path, frames) in std::mem::take(&mut self.pending_files) {
+            let mut entry = Entry::loading(path);
+            for frame in frames {
+                entry.tag.add_frame(frame);
+            }
+            entries.push(entry);
+        }
+        AppEvent::AddFiles(entries)
+    }
+
+    pub fn popup_widget(&self) -> Option<&dyn Popup> {
+        self.popup_stack.last().map(|popup| popup.as_ref())
     }
 
     pub fn spawn_help_popup(&mut self, help_popup: HelpPopup) {
@@ -230,6 +632,12 @@ impl FilesState {
         let parent_dir = config.get_key(&Action::ParentDir).unwrap();
         let add = config.get_key(&Action::AddFile).unwrap();
         let add_all = config.get_key(&Action::AddAllFiles).unwrap();
+        let add_recursive = config.get_key(&Action::AddRecursive).unwrap();
+        let toggle_mark = config.get_key(&Action::ToggleMark).unwrap();
+        let toggle_preview = config.get_key(&Action::TogglePreview).unwrap();
+        let mark_range = config.get_key(&Action::MarkRange).unwrap();
+        let tags_from_path = config.get_key(&Action::TagsFromPath).unwrap();
+        let fuzzy_find = config.get_key(&Action::FuzzyFind).unwrap();
 
         self.help_text = vec![
             format!("`{}` - Quit", util::display_keycode(quit)),
@@ -237,65 +645,125 @@ impl FilesState {
                 "`{}` - Change to parent directory",
                 util::display_keycode(parent_dir)
             ),
-            format!("`{}` - Add highlighted file", util::display_keycode(add)),
+            format!(
+                "`{}` - Add marked files and directories, or the highlighted file",
+                util::display_keycode(add)
+            ),
+            format!(
+                "`{}` - Mark highlighted file or directory",
+                util::display_keycode(toggle_mark)
+            ),
+            format!(
+                "`{}` - Mark everything from the last marked item to the highlighted one",
+                util::display_keycode(mark_range)
+            ),
             format!("`{}` - Add all files", util::display_keycode(add_all)),
+            format!(
+                "`{}` - Add all files in this directory and its subdirectories",
+                util::display_keycode(add_recursive)
+            ),
+            format!(
+                "`{}` - Add files below this directory with frames from their paths",
+                util::display_keycode(tags_from_path)
+            ),
+            format!(
+                "`{}` - Find files by typing part of their name",
+                util::display_keycode(fuzzy_find)
+            ),
+            format!(
+                "`{}` - Show or hide the tag preview",
+                util::display_keycode(toggle_preview)
+            ),
         ];
     }
 }
 
-// Get a Vec of (Path, Tags) from a Vec of DirEntrys
-fn get_tags(entries: &[FilesStateItem]) -> Result<Vec<Entry>, anyhow::Error> {
-    let tags = entries
+// Get entries waiting for their tags to be read from a Vec of DirEntrys
+fn loading_entries(entries: &[FilesStateItem]) -> Vec<Entry> {
+    entries
         .iter()
         .filter_map(|entry| match entry {
             FilesStateItem::DirEntry(entry) => match entry.path().is_dir() {
-                false => {
-                    let tag = match Tag::read_from_path(entry.path()) {
-                        Ok(tag) => tag,
-                        Err(id3::Error {
-                            kind: id3::ErrorKind::NoTag,
-                            ..
-                        }) => {
-                            // TODO - Trigger show logs from here
-                            warn!("File has no id3 tag, adding empty tag");
-                            Tag::new()
-                        }
-                        Err(e) => {
-                            error!("Failed to add file - {}", e);
-                            return None;
-                        }
-                    };
-                    Some(Entry::new(entry.path(), tag))
-                }
+                false => Some(Entry::loading(entry.path())),
                 true => None,
             },
             FilesStateItem::Parent => unreachable!(),
         })
-        .collect();
+        .collect()
+}
+
+// Read the tag of a single file
+pub fn read_tag(path: &Path) -> Option<Tag> {
+    let backend = match backend_for(path) {
+        Some(backend) => backend,
+        None => {
+            error!("Failed to add {:?} - unsupported file type", path);
+            return None;
+        }
+    };
+    match backend.read(path) {
+        Ok(tag) => Some(tag),
+        Err(id3::Error {
+            kind: id3::ErrorKind::NoTag,
+            ..
+        }) => {
+            warn!("{:?} has no id3 tag, adding empty tag", path);
+            Some(Tag::new())
+        }
+        Err(e) => {
+            error!("Failed to add {:?} - {}", path, e);
+            None
+        }
+    }
+}
 
-    Ok(tags)
+// Get the paths of all files in `dir` and its subdirectories, down to `max_depth`
+// levels below `dir`
+pub fn walk_files(
+    dir: &Path,
+    show_hidden_dirs: bool,
+    max_depth: usize,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut items = get_entries(dir, show_hidden_dirs)?;
+    sort_files(&mut items);
+
+    let mut paths = vec![];
+    for item in items {
+        if let FilesStateItem::DirEntry(entry) = item {
+            if entry.file_type()?.is_dir() {
+                if max_depth > 0 {
+                    paths.append(&mut walk_files(
+                        &entry.path(),
+                        show_hidden_dirs,
+                        max_depth - 1,
+                    )?);
+                }
+            } else {
+                paths.push(entry.path());
+            }
+        }
+    }
+
+    Ok(paths)
 }
 
-// Get a Vec<FilesStateItem> from a Path, filters out everything except .mp3 and other directories
+// Get a Vec<FilesStateItem> from a Path, filters out everything except
+// directories and files with a tag backend
 fn get_entries(path: &Path, show_hidden_dirs: bool) -> Result<Vec<FilesStateItem>, anyhow::Error> {
     let mut files = vec![FilesStateItem::Parent]; // Add `../` item
-    let mut entries = fs::read_dir(&path)?
+    let mut entries = fs::read_dir(path)?
         .filter_map(|rdir| {
             let rdir = rdir.unwrap();
             if rdir.file_type().unwrap().is_dir() {
                 if rdir.file_name().to_str().unwrap().starts_with(".") && !show_hidden_dirs {
-                    return None;
-                } else {
-                    return Some(FilesStateItem::DirEntry(rdir));
-                }
-            } else if let Some(ext) = rdir.path().extension() {
-                if ext.to_str() == Some("mp3") {
-                    return Some(FilesStateItem::DirEntry(rdir));
+                    None
                 } else {
-                    return None;
+                    Some(FilesStateItem::DirEntry(rdir))
                 }
+            } else if backend_for(&rdir.path()).is_some() {
+                Some(FilesStateItem::DirEntry(rdir))
             } else {
-                return None;
+                None
             }
         })
         .collect();
@@ -306,12 +774,12 @@ fn get_entries(path: &Path, show_hidden_dirs: bool) -> Result<Vec<FilesStateItem
 }
 
 // Sort a list of `FilesStateItem`, directories first then by filename
-pub fn sort_files(files: &mut Vec<FilesStateItem>) {
+pub fn sort_files(files: &mut [FilesStateItem]) {
     files.sort_by(|a, b| {
         match (a, b) {
             // First item should always be `../`
             // Apparently `b` here is the first item in the Vec not `a`
-            (_, FilesStateItem::Parent) => return Ordering::Greater,
+            (_, FilesStateItem::Parent) => Ordering::Greater,
             (FilesStateItem::DirEntry(a), FilesStateItem::DirEntry(b)) => {
                 match (
                     a.file_type().unwrap().is_dir(),
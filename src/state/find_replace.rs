@@ -0,0 +1,178 @@
+use id3::{frame::ExtendedText, Content, Frame};
+use regex::{Regex, RegexBuilder};
+
+use crate::state::frame_data;
+
+// A find and replace operation over the text frames of a tag. Plain text searches
+// are escaped and compiled into a regex so both modes share the same code, in regex
+// mode the replacement can refer to capture groups with `$1` or `${name}`.
+pub struct FindReplace {
+    regex: Regex,
+    replacement: String,
+    // Frame ids the search is limited to, empty for all text frames
+    frame_ids: Vec<String>,
+}
+
+impl FindReplace {
+    pub fn new(
+        find: &str,
+        replace: &str,
+        use_regex: bool,
+        case_sensitive: bool,
+        scope: &str,
+    ) -> Result<Self, String> {
+        if find.is_empty() {
+            return Err("Find text cannot be empty".to_owned());
+        }
+
+        let (expr, replacement) = match use_regex {
+            true => (find.to_owned(), replace.to_owned()),
+            // `$` is the only special character in a replacement string
+            false => (regex::escape(find), replace.replace('$', "$$")),
+        };
+        let regex = RegexBuilder::new(&expr)
+            .case_insensitive(!case_sensitive)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            regex,
+            replacement,
+            frame_ids: parse_frame_ids(scope),
+        })
+    }
+
+    fn in_scope(&self, id: &str) -> bool {
+        self.frame_ids.is_empty() || self.frame_ids.iter().any(|i| i == id)
+    }
+
+    // Returns the frame with every match replaced and the number of matches, or
+    // `None` if the frame is out of scope or contains no matches
+    pub fn apply(&self, frame: &Frame) -> Option<(Frame, usize)> {
+        if !self.in_scope(frame.id()) {
+            return None;
+        }
+
+        match frame.content() {
+            Content::Text(text) => {
+                let count = self.regex.find_iter(text).count();
+                if count == 0 {
+                    return None;
+                }
+                let new_text = self.regex.replace_all(text, self.replacement.as_str());
+                Some((Frame::text(frame.id(), new_text), count))
+            }
+            Content::ExtendedText(ExtendedText { description, value }) => {
+                let count = self.regex.find_iter(value).count();
+                if count == 0 {
+                    return None;
+                }
+                let new_value = self.regex.replace_all(value, self.replacement.as_str());
+                let content = Content::ExtendedText(ExtendedText {
+                    description: description.clone(),
+                    value: new_value.into_owned(),
+                });
+                Some((Frame::with_content(frame.id(), content), count))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Parse a list of frames separated by commas or spaces. Both frame ids (`TPE1`) and
+// the names used in templates (`artist`) are accepted.
+pub fn parse_frame_ids(text: &str) -> Vec<String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| frame_data::name_to_id(&s.to_lowercase()).unwrap_or_else(|_| s.to_uppercase()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(find_replace: &FindReplace, frame: Frame) -> Option<(String, usize)> {
+        find_replace.apply(&frame).map(|(frame, count)| {
+            let text = match frame.content() {
+                Content::ExtendedText(ext) => format!("{}={}", ext.description, ext.value),
+                content => content.text().unwrap().to_owned(),
+            };
+            (text, count)
+        })
+    }
+
+    #[test]
+    fn plain_text_is_escaped() {
+        let find_replace = FindReplace::new("a.b", "$1 c", false, false, "").unwrap();
+        assert_eq!(
+            replace(&find_replace, Frame::text("TIT2", "A.B axb a.b")),
+            Some(("$1 c axb $1 c".to_owned(), 2))
+        );
+        assert_eq!(replace(&find_replace, Frame::text("TIT2", "axb")), None);
+    }
+
+    #[test]
+    fn case_sensitive() {
+        let find_replace = FindReplace::new("live", "", false, true, "").unwrap();
+        assert_eq!(replace(&find_replace, Frame::text("TIT2", "Live")), None);
+        assert_eq!(
+            replace(&find_replace, Frame::text("TIT2", "Song (live)")),
+            Some(("Song ()".to_owned(), 1))
+        );
+    }
+
+    #[test]
+    fn regex_capture_groups() {
+        let find_replace = FindReplace::new(
+            r"(?P<artist>.+) feat\. (.+)",
+            "$2 & ${artist}",
+            true,
+            false,
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            replace(&find_replace, Frame::text("TPE1", "A feat. B")),
+            Some(("B & A".to_owned(), 1))
+        );
+        assert!(FindReplace::new("(", "", true, false, "").is_err());
+        assert!(FindReplace::new("", "x", false, false, "").is_err());
+    }
+
+    #[test]
+    fn scope_limits_frames() {
+        let find_replace = FindReplace::new("a", "b", false, false, "artist, TALB").unwrap();
+        assert_eq!(replace(&find_replace, Frame::text("TIT2", "a")), None);
+        assert_eq!(
+            replace(&find_replace, Frame::text("TPE1", "a")),
+            Some(("b".to_owned(), 1))
+        );
+        assert!(find_replace.apply(&Frame::text("TALB", "a")).is_some());
+    }
+
+    #[test]
+    fn extended_text_value_only() {
+        let find_replace = FindReplace::new("mood", "feel", false, false, "").unwrap();
+        let frame = Frame::with_content(
+            "TXXX",
+            Content::ExtendedText(ExtendedText {
+                description: "MOOD".to_owned(),
+                value: "Mood: calm".to_owned(),
+            }),
+        );
+        assert_eq!(
+            replace(&find_replace, frame),
+            Some(("MOOD=feel: calm".to_owned(), 1))
+        );
+    }
+
+    #[test]
+    fn frame_ids_from_names() {
+        assert_eq!(
+            parse_frame_ids(" Artist,tpe2  TXXX,,"),
+            ["TPE1", "TPE2", "TXXX"]
+        );
+        assert!(parse_frame_ids(" , ").is_empty());
+    }
+}
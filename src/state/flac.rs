@@ -0,0 +1,390 @@
+use std::{
+    fs::{File, OpenOptions},
+    hash::Hasher,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use id3::{Error, ErrorKind, Tag};
+
+use crate::state::{
+    backend::{replace_file, TagBackend},
+    vorbis_comment::{add_pictures, parse_picture, picture_bytes, VorbisComments},
+};
+
+const STREAMINFO: u8 = 0;
+const PADDING: u8 = 1;
+const VORBIS_COMMENT: u8 = 4;
+const PICTURE: u8 = 6;
+
+// Padding left after the metadata when the file has to be rewritten, so later
+// edits can be written in place
+const NEW_PADDING: usize = 4096;
+const MAX_BLOCK_LEN: usize = (1 << 24) - 1;
+
+// VORBIS_COMMENT and PICTURE metadata blocks of a FLAC file
+pub struct Flac;
+
+impl TagBackend for Flac {
+    fn read(&self, path: &Path) -> id3::Result<Tag> {
+        let metadata = Metadata::read(&mut File::open(path)?)?;
+
+        let comments = metadata.block(VORBIS_COMMENT);
+        let pictures: Vec<&Vec<u8>> = metadata.blocks_of(PICTURE).collect();
+        if comments.is_none() && pictures.is_empty() {
+            return Err(Error::new(ErrorKind::NoTag, "No vorbis comment found"));
+        }
+
+        let mut tag = match comments {
+            Some(data) => VorbisComments::parse(data)?.to_tag(),
+            None => Tag::new(),
+        };
+        let pictures = pictures
+            .into_iter()
+            .map(|data| parse_picture(data))
+            .collect::<id3::Result<Vec<_>>>()?;
+        add_pictures(&mut tag, pictures);
+        Ok(tag)
+    }
+
+    fn write(&self, tag: &Tag, path: &Path) -> id3::Result<()> {
+        let metadata = Metadata::read(&mut File::open(path)?)?;
+
+        let mut comments = match metadata.block(VORBIS_COMMENT) {
+            Some(data) => VorbisComments::parse(data)?,
+            None => VorbisComments::default(),
+        };
+        comments.set_from_tag(tag);
+
+        // STREAMINFO and any other blocks are kept as they are, in their order
+        let mut blocks: Vec<(u8, Vec<u8>)> = metadata
+            .blocks
+            .iter()
+            .filter(|(kind, _)| ![VORBIS_COMMENT, PICTURE].contains(kind))
+            .cloned()
+            .collect();
+        blocks.push((VORBIS_COMMENT, comments.to_bytes()));
+        for picture in tag.pictures() {
+            blocks.push((PICTURE, picture_bytes(picture)));
+        }
+        if blocks.iter().any(|(_, data)| data.len() > MAX_BLOCK_LEN) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Metadata block is too large for a FLAC file",
+            ));
+        }
+
+        let size: usize = blocks.iter().map(|(_, data)| 4 + data.len()).sum();
+        let available = (metadata.audio_start - metadata.start - 4) as usize;
+        if size == available || size + 4 <= available {
+            if size < available {
+                blocks.push((PADDING, vec![0; available - size - 4]));
+            }
+            let mut file = OpenOptions::new().write(true).open(path)?;
+            file.seek(SeekFrom::Start(metadata.start + 4))?;
+            file.write_all(&encode_blocks(&blocks))?;
+            return Ok(());
+        }
+
+        blocks.push((PADDING, vec![0; NEW_PADDING]));
+        rewrite(path, &metadata, &encode_blocks(&blocks))
+    }
+
+    fn hash_audio(&self, path: &Path, state: &mut dyn Hasher) -> id3::Result<()> {
+        let mut file = File::open(path)?;
+        let metadata = Metadata::read(&mut file)?;
+        file.seek(SeekFrom::Start(metadata.audio_start))?;
+        let mut data = vec![];
+        file.read_to_end(&mut data)?;
+        state.write(&data);
+        Ok(())
+    }
+
+    fn tag_name(&self, _tag: &Tag) -> String {
+        "FLAC vorbis comment".to_owned()
+    }
+}
+
+// The metadata blocks at the start of a FLAC file, padding is left out
+struct Metadata {
+    // Offset of the `fLaC` marker, non zero if the file starts with an ID3v2 tag
+    start: u64,
+    blocks: Vec<(u8, Vec<u8>)>,
+    // Offset of the first audio frame
+    audio_start: u64,
+}
+
+impl Metadata {
+    fn read(file: &mut File) -> id3::Result<Self> {
+        let file_len = file.metadata()?.len();
+        // A file cut short is reported as a broken FLAC file, not as an io error
+        let truncated = |e: io::Error| match e.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                Error::new(ErrorKind::Parsing, "FLAC file ends inside its metadata")
+            }
+            _ => e.into(),
+        };
+
+        let mut header = [0; 10];
+        file.read_exact(&mut header[..4]).map_err(truncated)?;
+
+        // Some programs put an ID3v2 tag in front of the stream, it is skipped
+        let mut start = 0;
+        if &header[..3] == b"ID3" {
+            file.read_exact(&mut header[4..]).map_err(truncated)?;
+            let size = header[6..10]
+                .iter()
+                .fold(0u64, |size, b| (size << 7) | (*b & 0x7f) as u64);
+            let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+            start = 10 + size + footer;
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut header[..4]).map_err(truncated)?;
+        }
+        if &header[..4] != b"fLaC" {
+            return Err(Error::new(ErrorKind::Parsing, "Not a FLAC file"));
+        }
+
+        let mut blocks = vec![];
+        let mut position = start + 4;
+        loop {
+            let mut block_header = [0; 4];
+            file.read_exact(&mut block_header).map_err(truncated)?;
+            let last = block_header[0] & 0x80 != 0;
+            let kind = block_header[0] & 0x7f;
+            let len = u32::from_be_bytes([0, block_header[1], block_header[2], block_header[3]]);
+            if position + 4 + len as u64 > file_len {
+                return Err(Error::new(
+                    ErrorKind::Parsing,
+                    "FLAC metadata block is longer than the rest of the file",
+                ));
+            }
+
+            if kind == PADDING {
+                file.seek(SeekFrom::Current(len as i64))?;
+            } else {
+                let mut data = vec![0; len as usize];
+                file.read_exact(&mut data).map_err(truncated)?;
+                blocks.push((kind, data));
+            }
+            position += 4 + len as u64;
+            if last {
+                break;
+            }
+        }
+
+        if blocks.first().map(|(kind, _)| *kind) != Some(STREAMINFO) {
+            return Err(Error::new(
+                ErrorKind::Parsing,
+                "FLAC file has no STREAMINFO",
+            ));
+        }
+
+        Ok(Self {
+            start,
+            blocks,
+            audio_start: position,
+        })
+    }
+
+    fn block(&self, kind: u8) -> Option<&Vec<u8>> {
+        self.blocks_of(kind).next()
+    }
+
+    fn blocks_of(&self, kind: u8) -> impl Iterator<Item = &Vec<u8>> {
+        self.blocks
+            .iter()
+            .filter(move |(k, _)| *k == kind)
+            .map(|(_, data)| data)
+    }
+}
+
+fn encode_blocks(blocks: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for (i, (kind, data)) in blocks.iter().enumerate() {
+        let last = if i == blocks.len() - 1 { 0x80 } else { 0 };
+        bytes.push(kind | last);
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+        bytes.extend_from_slice(data);
+    }
+    bytes
+}
+
+// Write the file again with new metadata when it does not fit in the old space
+fn rewrite(path: &Path, metadata: &Metadata, blocks: &[u8]) -> id3::Result<()> {
+    let mut old = File::open(path)?;
+    replace_file(path, |new| {
+        io::copy(&mut (&mut old).take(metadata.start), new)?;
+        new.write_all(b"fLaC")?;
+        new.write_all(blocks)?;
+        old.seek(SeekFrom::Start(metadata.audio_start))?;
+        io::copy(&mut old, new)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use id3::{
+        frame::{Picture, PictureType},
+        TagLike,
+    };
+
+    use super::*;
+    use crate::state::test_dir::TestDir;
+
+    const AUDIO: &[u8] = b"\xff\xf8audio frames";
+
+    // A FLAC file with a STREAMINFO, VORBIS_COMMENT and optional PADDING block,
+    // optionally behind an ID3v2 tag
+    fn flac_file(dir: &TestDir, padding: Option<usize>, id3: bool) -> PathBuf {
+        let comments = VorbisComments {
+            vendor: "test".to_owned(),
+            fields: vec![
+                ("TITLE".to_owned(), "Song".to_owned()),
+                ("MOOD".to_owned(), "Calm".to_owned()),
+            ],
+            trailing: vec![],
+        };
+        let mut bytes = vec![];
+        if id3 {
+            bytes.extend_from_slice(b"ID3\x04\0\0\0\0\0\x02\0\0");
+        }
+        bytes.extend_from_slice(b"fLaC");
+        let mut blocks = vec![
+            (STREAMINFO, vec![7; 34]),
+            (VORBIS_COMMENT, comments.to_bytes()),
+        ];
+        if let Some(padding) = padding {
+            blocks.push((PADDING, vec![0; padding]));
+        }
+        bytes.extend_from_slice(&encode_blocks(&blocks));
+        bytes.extend_from_slice(AUDIO);
+        dir.file("a.flac", &bytes)
+    }
+
+    #[test]
+    fn write_in_place() {
+        let dir = TestDir::new("flac");
+        let path = flac_file(&dir, Some(200), false);
+        let len = fs::metadata(&path).unwrap().len();
+        let mut tag = Flac.read(&path).unwrap();
+        assert_eq!(tag.title(), Some("Song"));
+        tag.set_title("A longer title");
+        Flac.write(&tag, &path).unwrap();
+
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+        let tag = Flac.read(&path).unwrap();
+        assert_eq!(tag.title(), Some("A longer title"));
+        assert_eq!(tag.extended_texts().next().unwrap().value, "Calm");
+        assert!(fs::read(&path).unwrap().ends_with(AUDIO));
+    }
+
+    #[test]
+    fn write_exact_fit() {
+        let dir = TestDir::new("flac");
+        let path = flac_file(&dir, None, false);
+        let len = fs::metadata(&path).unwrap().len();
+        let mut tag = Flac.read(&path).unwrap();
+        tag.set_title("Tune");
+        Flac.write(&tag, &path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+        assert_eq!(Flac.read(&path).unwrap().title(), Some("Tune"));
+
+        // Two bytes left over is too little for a padding block
+        tag.set_title("So");
+        Flac.write(&tag, &path).unwrap();
+        assert!(fs::metadata(&path).unwrap().len() > len);
+        assert_eq!(Flac.read(&path).unwrap().title(), Some("So"));
+        assert!(fs::read(&path).unwrap().ends_with(AUDIO));
+    }
+
+    #[test]
+    fn write_rewrites_file() {
+        let dir = TestDir::new("flac");
+        let path = flac_file(&dir, Some(10), true);
+        let mut tag = Flac.read(&path).unwrap();
+        let picture = Picture {
+            mime_type: "image/png".to_owned(),
+            picture_type: PictureType::CoverFront,
+            description: "cover".to_owned(),
+            data: vec![5; 1000],
+        };
+        tag.add_frame(picture.clone());
+        Flac.write(&tag, &path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"ID3"));
+        assert!(bytes.ends_with(AUDIO));
+        let metadata = Metadata::read(&mut File::open(&path).unwrap()).unwrap();
+        assert_eq!(metadata.start, 12);
+        assert_eq!(metadata.block(STREAMINFO), Some(&vec![7; 34]));
+        let tag = Flac.read(&path).unwrap();
+        assert_eq!(tag.title(), Some("Song"));
+        assert!(tag.pictures().eq([&picture]));
+
+        // The new padding leaves room for the next edit
+        let len = bytes.len();
+        let mut tag = tag;
+        tag.set_title("Other");
+        Flac.write(&tag, &path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len() as usize, len);
+    }
+
+    #[test]
+    fn read_without_streaminfo() {
+        let dir = TestDir::new("flac");
+        let mut bytes = b"fLaC".to_vec();
+        bytes.extend_from_slice(&encode_blocks(&[(PADDING, vec![0; 4])]));
+        let path = dir.file("a.flac", &bytes);
+        assert!(Flac.read(&path).is_err());
+    }
+
+    #[test]
+    fn read_truncated() {
+        let dir = TestDir::new("flac");
+        let streaminfo = encode_blocks(&[(STREAMINFO, vec![7; 34])]);
+        let mut not_last = streaminfo.clone();
+        not_last[0] = STREAMINFO;
+        let cases = [
+            (
+                [b"fLaC", &streaminfo[..20]].concat(),
+                "FLAC metadata block is longer than the rest of the file",
+            ),
+            (
+                [b"fLaC", &not_last[..]].concat(),
+                "FLAC file ends inside its metadata",
+            ),
+            (
+                b"ID3\x04\0\0\0\0\0\x7f".to_vec(),
+                "FLAC file ends inside its metadata",
+            ),
+        ];
+        for (bytes, message) in cases {
+            let path = dir.file("a.flac", &bytes);
+            let e = Flac.read(&path).err().unwrap();
+            assert!(matches!(e.kind, ErrorKind::Parsing));
+            assert_eq!(e.description, message);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rewrite_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("flac");
+        let path = flac_file(&dir, None, false);
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o604)).unwrap();
+        let mut tag = Flac.read(&path).unwrap();
+        tag.set_title("A title too long to fit in place");
+        Flac.write(&tag, &path).unwrap();
+
+        assert_eq!(Flac.read(&path).unwrap().title(), tag.title());
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o604
+        );
+    }
+}
@@ -60,11 +60,15 @@ pub struct FrameData {
 pub fn id_to_name(id: &str) -> Result<String, String> {
     // debug!("id_to_name: {}", id);
     match id {
+        "TIT1" => Ok("Content Group".to_string()),
         "TIT2" => Ok("Title".to_string()),
         "TALB" => Ok("Album".to_string()),
         "TPE1" => Ok("Artist".to_string()),
+        "TPE2" => Ok("Album Artist".to_string()),
         "TRCK" => Ok("Track".to_string()),
+        "TPOS" => Ok("Disc".to_string()),
         "TDRC" => Ok("Date".to_string()),
+        "TCON" => Ok("Genre".to_string()),
         "TXXX" => Ok("User Defined Text".to_string()),
         _ => Err("Frame not supported".to_string()),
     }
@@ -72,9 +76,15 @@ pub fn id_to_name(id: &str) -> Result<String, String> {
 
 pub fn name_to_id(name: &str) -> Result<String, String> {
     match name {
-        // TODO - Add all necessary names
+        "group" => Ok("TIT1".to_string()),
         "title" => Ok("TIT2".to_string()),
+        "album" => Ok("TALB".to_string()),
+        "artist" => Ok("TPE1".to_string()),
+        "albumartist" => Ok("TPE2".to_string()),
         "track" => Ok("TRCK".to_string()),
+        "disc" => Ok("TPOS".to_string()),
+        "date" => Ok("TDRC".to_string()),
+        "genre" => Ok("TCON".to_string()),
         _ => Err("Name not recognised".to_string()),
     }
 }
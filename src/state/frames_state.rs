@@ -31,7 +31,7 @@ impl FramesState {
     pub fn handle_input(
         &mut self,
         key: &KeyEvent,
-        actions: &Vec<Action>,
+        actions: &[Action],
         show_logs: &mut bool,
     ) -> AppEvent {
         let action = if actions.len() == 1 {
@@ -101,8 +101,8 @@ impl FramesState {
         }
     }
 
-    pub fn popup_widget(&self) -> Option<&Box<dyn Popup>> {
-        self.popup_stack.last()
+    pub fn popup_widget(&self) -> Option<&dyn Popup> {
+        self.popup_stack.last().map(|popup| popup.as_ref())
     }
 
     pub fn spawn_help_popup(&mut self, help_popup: HelpPopup) {
@@ -0,0 +1,147 @@
+use std::cmp::Reverse;
+
+use tui::widgets::ListState;
+
+// A fuzzy match of a query against one item of a list
+pub struct FuzzyMatch {
+    // Index of the item in the full list
+    pub index: usize,
+    // Indices of the chars of the item that matched the query
+    pub positions: Vec<usize>,
+    score: i64,
+}
+
+// Type to filter state for a list, `matches` holds the items matching `query`
+// with the best matches first
+pub struct Finder {
+    pub query: String,
+    pub matches: Vec<FuzzyMatch>,
+    pub list_state: ListState,
+}
+
+impl Finder {
+    pub fn new(names: &[String]) -> Self {
+        let mut finder = Self {
+            query: "".to_owned(),
+            matches: vec![],
+            list_state: ListState::default(),
+        };
+        finder.update(names);
+        finder
+    }
+
+    // Match `names` against the query again, the best match is highlighted
+    pub fn update(&mut self, names: &[String]) {
+        self.matches = names
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| {
+                fuzzy_match(&self.query, name).map(|(score, positions)| FuzzyMatch {
+                    index,
+                    positions,
+                    score,
+                })
+            })
+            .collect();
+        // Stable sort so equal scores keep the order of the list
+        self.matches.sort_by_key(|m| Reverse(m.score));
+
+        self.list_state.select(match self.matches.is_empty() {
+            true => None,
+            false => Some(0),
+        });
+    }
+
+    // Index in the full list of the highlighted match
+    pub fn selected(&self) -> Option<usize> {
+        self.list_state
+            .selected()
+            .and_then(|i| self.matches.get(i))
+            .map(|m| m.index)
+    }
+}
+
+// Match the chars of `pattern` in order against `text` ignoring case. Returns a
+// score, higher for consecutive chars and chars at the start of words, and the
+// char indices of `text` that matched.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut positions = vec![];
+    let mut score = 0;
+    let mut start = 0;
+
+    for p in pattern.chars().flat_map(char::to_lowercase) {
+        let offset = chars[start..]
+            .iter()
+            .position(|c| c.to_lowercase().any(|c| c == p))?;
+        let pos = start + offset;
+
+        score += 1;
+        match positions.last() {
+            Some(prev) if pos == prev + 1 => score += 5,
+            Some(prev) => score -= (pos - prev - 1).min(5) as i64,
+            None => score -= pos.min(5) as i64,
+        }
+        if pos == 0 || is_word_start(chars[pos - 1], chars[pos]) {
+            score += 3;
+        }
+
+        positions.push(pos);
+        start = pos + 1;
+    }
+
+    Some((score, positions))
+}
+
+fn is_word_start(prev: char, c: char) -> bool {
+    matches!(prev, ' ' | '-' | '_' | '.' | '/') || (prev.is_lowercase() && c.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn match_positions() {
+        assert_eq!(fuzzy_match("abc", "a-b-c").unwrap().1, [0, 2, 4]);
+        assert_eq!(fuzzy_match("AB", "xaxb").unwrap().1, [1, 3]);
+        assert_eq!(fuzzy_match("ba", "ab"), None);
+        assert_eq!(fuzzy_match("", "ab"), Some((0, vec![])));
+    }
+
+    #[test]
+    fn consecutive_and_word_starts_score_higher() {
+        let score = |pattern, text| fuzzy_match(pattern, text).unwrap().0;
+        assert!(score("abc", "abcx") > score("abc", "axbxc"));
+        assert!(score("ws", "word_start") > score("ws", "towns"));
+        assert!(score("cs", "camelSong") > score("cs", "cats"));
+    }
+
+    #[test]
+    fn ranking_ties_keep_list_order() {
+        let list = names(&["b.mp3", "a.mp3", "ab.mp3", "c.flac"]);
+        let mut finder = Finder::new(&list);
+        assert_eq!(finder.matches.len(), 4);
+
+        finder.query = "mp3".to_owned();
+        finder.update(&list);
+        let order: Vec<usize> = finder.matches.iter().map(|m| m.index).collect();
+        assert_eq!(order, [0, 1, 2]);
+        assert_eq!(finder.selected(), Some(0));
+
+        finder.query = "zz".to_owned();
+        finder.update(&list);
+        assert!(finder.matches.is_empty());
+        assert_eq!(finder.selected(), None);
+    }
+
+    #[test]
+    fn non_ascii_case() {
+        let (_, positions) = fuzzy_match("É", "café").unwrap();
+        assert_eq!(positions, [3]);
+    }
+}
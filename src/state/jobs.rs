@@ -0,0 +1,365 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+use id3::Tag;
+use log::{info, warn};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::state::{
+    backend::backend_for,
+    duplicates::audio_hash,
+    files_state::{read_tag, walk_files},
+    mpeg::StreamInfo,
+    replaygain::Loudness,
+    tag_preview::TagPreview,
+    verify::verify,
+};
+
+// Upper limit on the number of threads reading tags
+const MAX_WORKERS: usize = 8;
+
+// Results sent from background jobs to the main loop
+pub enum JobEvent {
+    // Files found by a recursive add, their tags are read by the `TagLoader`
+    Found(Vec<PathBuf>, Arc<Progress>),
+    // The tag and audio properties of a file have been read, the tag is `None` if it
+    // could not be read
+    Loaded(PathBuf, Option<Tag>, Option<StreamInfo>),
+    // The job loading a file was cancelled before its tag was read
+    Cancelled(PathBuf),
+    // A job has ended, holds the number of files that could not be read
+    Finished(usize),
+    // The tags of a file highlighted in the files screen have been read
+    Preview(PathBuf, TagPreview),
+    // The problems found in each verified file, empty if the job was cancelled
+    Verified(Vec<(PathBuf, Vec<String>)>),
+    // The loudness of each analysed file, `None` if it could not be decoded. Empty if
+    // the job was cancelled.
+    Analysed(Vec<(PathBuf, Option<Loudness>)>),
+    // The audio hash of each file, `None` if it could not be read. Empty if the job
+    // was cancelled.
+    Hashed(Vec<(PathBuf, Option<u64>)>),
+}
+
+// Progress of a background job, shared between the threads doing the work and the
+// popup showing it. `total` is zero until the number of files is known.
+#[derive(Default)]
+pub struct Progress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+    failed: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+struct LoadRequest {
+    path: PathBuf,
+    progress: Option<Arc<Progress>>,
+}
+
+// A pool of threads reading tags so that slow disks never block the ui. Results are
+// sent to the main loop as `JobEvent::Loaded` in the order they finish. The threads
+// exit when the loader is dropped.
+pub struct TagLoader {
+    tx: mpsc::Sender<LoadRequest>,
+    events: UnboundedSender<JobEvent>,
+}
+
+impl TagLoader {
+    pub fn new(events: UnboundedSender<JobEvent>) -> Self {
+        let (tx, rx) = mpsc::channel::<LoadRequest>();
+        let rx = Arc::new(Mutex::new(rx));
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_WORKERS);
+
+        for _ in 0..workers {
+            let rx = rx.clone();
+            let worker_events = events.clone();
+            thread::spawn(move || loop {
+                // The lock is released as soon as a request is received
+                let request = match rx.lock().unwrap().recv() {
+                    Ok(request) => request,
+                    Err(_) => break,
+                };
+                load(request, &worker_events);
+            });
+        }
+
+        Self { tx, events }
+    }
+
+    // Queue the tags of `paths` to be read, `progress` tracks a job made up of every
+    // path in this call
+    pub fn load(&self, paths: Vec<PathBuf>, progress: Option<Arc<Progress>>) {
+        if let Some(progress) = &progress {
+            progress.total.store(paths.len(), Ordering::Relaxed);
+            // No worker will finish the job if there is nothing to load
+            if paths.is_empty() {
+                info!("No new files found");
+                let _ = self.events.send(JobEvent::Finished(0));
+            }
+        }
+        for path in paths {
+            let request = LoadRequest {
+                path,
+                progress: progress.clone(),
+            };
+            if self.tx.send(request).is_err() {
+                warn!("Tag loader has stopped");
+                return;
+            }
+        }
+    }
+}
+
+fn load(request: LoadRequest, events: &UnboundedSender<JobEvent>) {
+    let LoadRequest { path, progress } = request;
+    if progress.as_ref().is_some_and(|p| p.is_cancelled()) {
+        let _ = events.send(JobEvent::Cancelled(path));
+    } else {
+        let tag = read_tag(&path);
+        let info = match tag {
+            Some(_) => backend_for(&path).and_then(|backend| backend.stream_info(&path)),
+            None => None,
+        };
+
+        if let Some(progress) = &progress {
+            if tag.is_none() {
+                progress.failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let _ = events.send(JobEvent::Loaded(path, tag, info));
+    }
+
+    if let Some(progress) = progress {
+        if progress.done.fetch_add(1, Ordering::Relaxed) + 1 == progress.total() {
+            let failed = progress.failed.load(Ordering::Relaxed);
+            info!("Finished reading {} files", progress.total());
+            let _ = events.send(JobEvent::Finished(failed));
+        }
+    }
+}
+
+// Find every file below the directories in `paths` on a background thread, files in
+// `paths` are included as they are. The files found are sent back to the main loop to
+// be added and loaded.
+pub fn spawn_add_recursive(
+    paths: Vec<PathBuf>,
+    show_hidden_dirs: bool,
+    max_depth: Option<usize>,
+    progress: Arc<Progress>,
+    tx: UnboundedSender<JobEvent>,
+) {
+    tokio::task::spawn_blocking(move || {
+        let mut found = vec![];
+        for path in paths {
+            if !path.is_dir() {
+                found.push(path);
+                continue;
+            }
+            match walk_files(&path, show_hidden_dirs, max_depth.unwrap_or(usize::MAX)) {
+                Ok(mut files) => found.append(&mut files),
+                Err(e) => warn!("Could not read directory {:?} - {}", path, e),
+            }
+        }
+        let _ = tx.send(JobEvent::Found(found, progress));
+    });
+}
+
+// Read the preview of a file highlighted in the files screen on a background thread
+pub fn spawn_preview(path: PathBuf, tx: UnboundedSender<JobEvent>) {
+    tokio::task::spawn_blocking(move || {
+        let preview = TagPreview::read(&path);
+        let _ = tx.send(JobEvent::Preview(path, preview));
+    });
+}
+
+// Check `paths` for damaged tags and audio on a background thread
+pub fn spawn_verify(paths: Vec<PathBuf>, progress: Arc<Progress>, tx: UnboundedSender<JobEvent>) {
+    progress.total.store(paths.len(), Ordering::Relaxed);
+    tokio::task::spawn_blocking(move || {
+        let mut results = vec![];
+        for path in paths {
+            if progress.is_cancelled() {
+                let _ = tx.send(JobEvent::Verified(vec![]));
+                return;
+            }
+            let problems = verify(&path);
+            results.push((path, problems));
+            progress.done.fetch_add(1, Ordering::Relaxed);
+        }
+        let _ = tx.send(JobEvent::Verified(results));
+    });
+}
+
+// Decode `paths` and measure their loudness on a background thread
+pub fn spawn_replaygain(
+    paths: Vec<PathBuf>,
+    progress: Arc<Progress>,
+    tx: UnboundedSender<JobEvent>,
+) {
+    progress.total.store(paths.len(), Ordering::Relaxed);
+    tokio::task::spawn_blocking(move || {
+        let mut results = vec![];
+        for path in paths {
+            let loudness = match Loudness::measure(&path, || progress.is_cancelled()) {
+                Ok(Some(loudness)) => Some(loudness),
+                Ok(None) => {
+                    let _ = tx.send(JobEvent::Analysed(vec![]));
+                    return;
+                }
+                Err(e) => {
+                    warn!("Could not decode {:?} - {}", path, e);
+                    None
+                }
+            };
+            results.push((path, loudness));
+            progress.done.fetch_add(1, Ordering::Relaxed);
+        }
+        let _ = tx.send(JobEvent::Analysed(results));
+    });
+}
+
+// Hash the audio data of `paths` on a background thread to find duplicate files
+pub fn spawn_hash_audio(
+    paths: Vec<PathBuf>,
+    progress: Arc<Progress>,
+    tx: UnboundedSender<JobEvent>,
+) {
+    progress.total.store(paths.len(), Ordering::Relaxed);
+    tokio::task::spawn_blocking(move || {
+        let mut results = vec![];
+        for path in paths {
+            if progress.is_cancelled() {
+                let _ = tx.send(JobEvent::Hashed(vec![]));
+                return;
+            }
+            let hash = match audio_hash(&path) {
+                Ok(hash) => Some(hash),
+                Err(e) => {
+                    warn!("Could not hash the audio of {:?} - {}", path, e);
+                    None
+                }
+            };
+            results.push((path, hash));
+            progress.done.fetch_add(1, Ordering::Relaxed);
+        }
+        let _ = tx.send(JobEvent::Hashed(results));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use id3::{TagLike, Version};
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    use super::*;
+    use crate::state::test_dir::TestDir;
+
+    // Events received up to and including the end of a job
+    fn job_events(rx: &mut UnboundedReceiver<JobEvent>) -> Vec<JobEvent> {
+        let mut events = vec![];
+        loop {
+            let event = rx.blocking_recv().unwrap();
+            let finished = matches!(event, JobEvent::Finished(_));
+            events.push(event);
+            if finished {
+                return events;
+            }
+        }
+    }
+
+    #[test]
+    fn loads_before_finishing() {
+        let dir = TestDir::new("loader");
+        let tagged = dir.file("a.mp3", &[0; 16]);
+        let mut tag = Tag::new();
+        tag.set_title("Song");
+        tag.write_to_path(&tagged, Version::Id3v24).unwrap();
+        let untagged = dir.file("b.mp3", &[0; 16]);
+        let unsupported = dir.file("c.txt", b"text");
+
+        let (tx, mut rx) = unbounded_channel();
+        let loader = TagLoader::new(tx);
+        let progress = Arc::new(Progress::default());
+        let paths = vec![tagged.clone(), untagged.clone(), unsupported.clone()];
+        loader.load(paths, Some(progress.clone()));
+
+        let events = job_events(&mut rx);
+        assert_eq!(events.len(), 4);
+        let mut loaded: Vec<(PathBuf, Option<Option<String>>)> = events[..3]
+            .iter()
+            .map(|event| match event {
+                JobEvent::Loaded(path, tag, _) => (
+                    path.clone(),
+                    tag.as_ref().map(|t| t.title().map(|s| s.to_owned())),
+                ),
+                _ => panic!("file not loaded before the job finished"),
+            })
+            .collect();
+        loaded.sort();
+        assert_eq!(
+            loaded,
+            [
+                (tagged, Some(Some("Song".to_owned()))),
+                (untagged, Some(None)),
+                (unsupported, None),
+            ]
+        );
+        assert!(matches!(events[3], JobEvent::Finished(1)));
+        assert_eq!(progress.done(), 3);
+    }
+
+    #[test]
+    fn cancelled_loads() {
+        let dir = TestDir::new("loader");
+        let paths = vec![dir.file("a.mp3", &[0; 16]), dir.file("b.mp3", &[0; 16])];
+
+        let (tx, mut rx) = unbounded_channel();
+        let loader = TagLoader::new(tx);
+        let progress = Arc::new(Progress::default());
+        progress.cancel();
+        loader.load(paths.clone(), Some(progress));
+
+        let events = job_events(&mut rx);
+        let mut cancelled: Vec<PathBuf> = events[..events.len() - 1]
+            .iter()
+            .map(|event| match event {
+                JobEvent::Cancelled(path) => path.clone(),
+                _ => panic!("file loaded after the job was cancelled"),
+            })
+            .collect();
+        cancelled.sort();
+        assert_eq!(cancelled, paths);
+        assert!(matches!(events.last(), Some(JobEvent::Finished(0))));
+
+        // A job with nothing to load finishes straight away
+        loader.load(vec![], Some(Arc::new(Progress::default())));
+        assert!(matches!(rx.blocking_recv(), Some(JobEvent::Finished(0))));
+    }
+}
@@ -1,22 +1,48 @@
 use std::{
     cmp::{Ord, Ordering, PartialOrd},
+    collections::HashMap,
     fs,
     path::PathBuf,
+    sync::Arc,
 };
 
-use crossterm::event::KeyEvent;
-use id3::{frame::ExtendedText, Content, Frame, Tag, TagLike, Version};
+use crossterm::event::{KeyCode, KeyEvent};
+use id3::{frame::ExtendedText, Content, Frame, Tag, TagLike};
 use log::{error, info, warn};
 use regex::{Match, Regex};
-use tui::widgets::ListState;
+use tokio::sync::mpsc::UnboundedSender;
+use tui::widgets::{ListState, TableState};
 
 use crate::{
-    configuration::{actions::Action, Config},
+    configuration::{actions::Action, validation::ValidationMode, Config},
     popups::{
-        double_input::DoubleInput, help::HelpPopup, single_input::SingleInput,
-        template::TemplateInput, Popup, PopupData, PopupHelpType,
+        double_input::DoubleInput,
+        form::{FormField, FormKind, FormPopup, FormValue},
+        help::HelpPopup,
+        progress::ProgressPopup,
+        single_input::SingleInput,
+        table::{TableKind, TablePopup},
+        template::TemplateInput,
+        Popup, PopupData, PopupHelpType,
+    },
+    state::{
+        album_lint::{lint, Fix},
+        backend::backend_for,
+        columns::Column,
+        duplicates::{group, DuplicateMode},
+        file_query::FileQuery,
+        find_replace::{parse_frame_ids, FindReplace},
+        frame_data,
+        jobs::{spawn_hash_audio, spawn_replaygain, spawn_verify, JobEvent, Progress},
+        mpeg::{self, StreamInfo},
+        replaygain::{format_gain, format_peak, Loudness},
+        sorting::{SortKey, SortValue},
+        tag_pattern::{TagPattern, TOKEN_REGEX},
+        transforms::Transform,
+        update_screen_state,
+        validation::Rules,
+        AppEvent, ScreenState,
     },
-    state::{frame_data, update_screen_state, AppEvent, ScreenState},
     util, LOGGER,
 };
 
@@ -28,12 +54,24 @@ pub enum Focus {
     // EditInput,
 }
 
+// Whether text typed into the search line jumps to the first match or hides the
+// files that don't match
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum SearchMode {
+    Search,
+    Filter,
+}
+
 #[derive(Clone)]
 pub struct Entry {
     pub path: PathBuf,
     pub filename: String,
     pub tag: Tag,
     pub selected: bool,
+    // The tag is still being read in the background
+    pub loading: bool,
+    // Audio properties, only read for mp3 files
+    pub info: Option<StreamInfo>,
 }
 
 impl Entry {
@@ -51,34 +89,58 @@ impl Entry {
             filename,
             tag,
             selected: false,
+            loading: false,
+            info: None,
+        }
+    }
+
+    // An entry with an empty tag, filled in by `MainState::finish_loading` once the
+    // tag has been read
+    pub fn loading(path: PathBuf) -> Self {
+        Self {
+            loading: true,
+            ..Self::new(path, Tag::new())
         }
     }
 }
 
+// A `{name}` token of the template string
+enum TemplateToken<'a> {
+    Frame(String),
+    // One of the audio properties in `mpeg::PROPERTIES`
+    Property(&'a str),
+}
+
 #[derive(PartialEq, Eq)]
 pub enum DetailItem {
     FileName(String),
     Frame(Frame),
+    // A frame whose value differs between the selected files, or that is missing
+    // from some of them. The frame holds the value from the first file.
+    Multiple(Frame),
+}
+
+impl DetailItem {
+    pub fn frame(&self) -> Option<&Frame> {
+        match self {
+            DetailItem::FileName(_) => None,
+            DetailItem::Frame(frame) | DetailItem::Multiple(frame) => Some(frame),
+        }
+    }
 }
 
 impl PartialOrd for DetailItem {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (self, other) {
-            (DetailItem::FileName(_), _) => Some(Ordering::Less),
-            (DetailItem::Frame(f), DetailItem::Frame(other_f)) => {
-                Some(f.name().cmp(other_f.name()))
-            }
-            (DetailItem::Frame(_), DetailItem::FileName(_)) => Some(Ordering::Greater),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for DetailItem {
     fn cmp(&self, other: &Self) -> Ordering {
-        match (self, other) {
-            (DetailItem::FileName(_), _) => Ordering::Less,
-            (DetailItem::Frame(f), DetailItem::Frame(other_f)) => f.name().cmp(other_f.name()),
-            (DetailItem::Frame(_), DetailItem::FileName(_)) => Ordering::Greater,
+        match (self.frame(), other.frame()) {
+            (None, _) => Ordering::Less,
+            (Some(f), Some(other_f)) => f.name().cmp(other_f.name()),
+            (Some(_), None) => Ordering::Greater,
         }
     }
 }
@@ -93,18 +155,84 @@ pub struct MainState {
 
     pub details_state: ListState,
     pub details: Vec<DetailItem>,
+    // Number of files shown together in the details list, zero when only the
+    // highlighted file is shown
+    pub merged_count: usize,
 
     help_text: Vec<String>,
     pub template_string: String,
     rex: Regex,
+    name_pattern: String,
+
+    // Frames waiting to be added to files once the user confirms a preview, holds
+    // the path of each file with its new frames. Paths are used as files can be
+    // removed by a background load while the preview is open.
+    pending_frames: Vec<(PathBuf, Vec<Frame>)>,
+    // Ids of the frames removed from each file before `pending_frames` are added
+    pending_removals: Vec<(PathBuf, Vec<String>)>,
+    // Last values entered in the find and replace popup
+    find_replace_values: Vec<FormValue>,
+
+    title_case_exceptions: Vec<String>,
+    transform_frames: Vec<String>,
+
+    // Frames copied from a file, ready to be pasted into others
+    clipboard: Vec<Frame>,
+    copy_frames: String,
+
+    // Show the files as a table instead of the files and details lists
+    pub table_view: bool,
+    // Keeps the scroll position of the table, the highlighted row follows `files_state`
+    pub table_state: TableState,
+    pub table_columns: Vec<Column>,
+    // Index of the highlighted column
+    pub table_column: usize,
+    // Index of the column the files are sorted by and whether the sort is descending
+    pub table_sort: Option<(usize, bool)>,
+    // Column of the highlighted file being edited by the active popup
+    table_edit: Option<usize>,
+    sort_values: Vec<FormValue>,
+
+    // Frame ids in the order they are shown in the details list
+    details_order: Vec<String>,
+    // Frame ids never shown in the details list
+    hidden_frames: Vec<String>,
+
+    // Text typed into the search line, `None` when the search line is closed
+    pub search_input: Option<String>,
+    search_mode: SearchMode,
+    // Index in `files` of the highlighted file when the search line was opened
+    search_origin: Option<usize>,
+    // Last search entered, used to jump between matches
+    search_query: Option<FileQuery>,
+    // Text of the active filter
+    pub filter: Option<String>,
+    // Query of the filter being typed or the active filter
+    filter_query: Option<FileQuery>,
+    // Indices in `files` of the files shown in the files list, in order. Files hidden
+    // by the filter stay in `files` and `files_state` indexes into this list.
+    visible: Vec<usize>,
+
+    job_tx: UnboundedSender<JobEvent>,
+    // Progress of the running background job
+    job: Option<Arc<Progress>>,
+    // Files removed because their tag could not be read, checked by the next verify
+    failed_files: Vec<PathBuf>,
+    duplicate_mode: DuplicateMode,
+    // Groups of duplicate files shown in the duplicates popup, the first file of each
+    // group is kept
+    duplicates: Vec<Vec<PathBuf>>,
+    // Fixes of the problems shown in the album lint popup, by row
+    lint_fixes: Vec<Option<Fix>>,
+    validation_rules: Rules,
 
     trigger_logs: bool,
 }
 
 impl MainState {
-    pub fn new(template_string: String) -> Self {
+    pub fn new(template_string: String, job_tx: UnboundedSender<JobEvent>) -> Self {
         let popup_stack: Vec<Box<dyn Popup>> = vec![];
-        let rex = Regex::new(r"\{[\w]+\}").unwrap();
+        let rex = Regex::new(TOKEN_REGEX).unwrap();
 
         Self {
             popup_stack,
@@ -113,9 +241,47 @@ impl MainState {
             files: vec![],
             details_state: ListState::default(),
             details: vec![],
+            merged_count: 0,
             help_text: vec![],
+            name_pattern: template_string.clone(),
             template_string,
             rex,
+            pending_frames: vec![],
+            pending_removals: vec![],
+            find_replace_values: vec![
+                FormValue::Text("".to_owned()),
+                FormValue::Text("".to_owned()),
+                FormValue::Toggle(false),
+                FormValue::Toggle(false),
+                FormValue::Text("".to_owned()),
+            ],
+            title_case_exceptions: vec![],
+            transform_frames: vec![],
+            clipboard: vec![],
+            copy_frames: "".to_owned(),
+            table_view: false,
+            table_state: TableState::default(),
+            table_columns: vec![Column::FileName],
+            table_column: 0,
+            table_sort: None,
+            table_edit: None,
+            sort_values: vec![FormValue::Choice(0), FormValue::Toggle(false)],
+            details_order: vec![],
+            hidden_frames: vec![],
+            search_input: None,
+            search_mode: SearchMode::Search,
+            search_origin: None,
+            search_query: None,
+            filter: None,
+            filter_query: None,
+            visible: vec![],
+            job_tx,
+            job: None,
+            failed_files: vec![],
+            duplicate_mode: DuplicateMode::Tags,
+            duplicates: vec![],
+            lint_fixes: vec![],
+            validation_rules: Rules::default(),
             trigger_logs: false,
         }
     }
@@ -123,9 +289,14 @@ impl MainState {
     pub fn handle_input(
         &mut self,
         key: &KeyEvent,
-        actions: &Vec<Action>,
+        actions: &[Action],
         show_logs: &mut bool,
     ) -> AppEvent {
+        if self.search_input.is_some() {
+            self.handle_search_input(key);
+            return AppEvent::None;
+        }
+
         // If the length of actions is greater than one then the KeyCode
         // pressed has been mapped to multiple actions. As long as the actions
         // have been configured properly there should only be one relevant
@@ -146,6 +317,34 @@ impl MainState {
                         || *a == Action::Remove
                         || *a == Action::SpawnPopup
                         || *a == Action::TemplatePopup
+                        || *a == Action::TagsFromName
+                        || *a == Action::NumberTracks
+                        || *a == Action::FindReplace
+                        || *a == Action::TitleCase
+                        || *a == Action::SentenceCase
+                        || *a == Action::UpperCase
+                        || *a == Action::LowerCase
+                        || *a == Action::TrimWhitespace
+                        || *a == Action::CollapseSpaces
+                        || *a == Action::NormaliseUnicode
+                        || *a == Action::CopyFrame
+                        || *a == Action::CopyTag
+                        || *a == Action::PasteMerge
+                        || *a == Action::PasteOverwrite
+                        || *a == Action::ToggleTableView
+                        || *a == Action::ColumnPrev
+                        || *a == Action::ColumnNext
+                        || *a == Action::SortColumn
+                        || *a == Action::SortFiles
+                        || *a == Action::Search
+                        || *a == Action::FilterFiles
+                        || *a == Action::SearchNext
+                        || *a == Action::SearchPrev
+                        || *a == Action::StreamInfo
+                        || *a == Action::Verify
+                        || *a == Action::ReplayGain
+                        || *a == Action::FindDuplicates
+                        || *a == Action::LintAlbum
                     {
                         action = *a;
                         break;
@@ -167,17 +366,24 @@ impl MainState {
             match popup.handle_input(key, action) {
                 AppEvent::ClosePopup => {
                     let _ = self.popup_stack.pop().unwrap();
+                    self.table_edit = None;
                 }
                 AppEvent::ClosePopupData(data) => {
                     let _ = self.popup_stack.pop().unwrap();
                     match data {
                         PopupData::SingleInput(text) => {
+                            let table_edit = self.table_edit.take();
                             if text.is_empty() {
                                 warn!("Text frame contained an empty field, not adding new frame");
                                 *show_logs = true;
                                 return AppEvent::None;
                             }
 
+                            if let Some(column) = table_edit {
+                                self.update_cell(column, text);
+                                return AppEvent::None;
+                            }
+
                             match self.details_state.selected() {
                                 Some(i) => {
                                     match &self.details[i] {
@@ -185,7 +391,7 @@ impl MainState {
                                             self.details[i] = DetailItem::FileName(text.clone());
                                             self.update_filename(text);
                                         }
-                                        DetailItem::Frame(frame) => {
+                                        DetailItem::Frame(frame) | DetailItem::Multiple(frame) => {
                                             let id = frame.id();
                                             let new_frame = Frame::text(id, text);
                                             self.details[i] = DetailItem::Frame(new_frame.clone());
@@ -200,7 +406,7 @@ impl MainState {
                         PopupData::DoubleInput(description, value) => {
                             let (prev_description, id, i) =
                                 if let Some(i) = self.details_state.selected() {
-                                    if let DetailItem::Frame(frame) = &self.details[i] {
+                                    if let Some(frame) = self.details[i].frame() {
                                         (
                                             frame
                                                 .content()
@@ -249,6 +455,37 @@ impl MainState {
                             self.template_string = text;
                             return AppEvent::UpdateConfig;
                         }
+                        PopupData::Form(FormKind::TagsFromName, values) => {
+                            self.preview_tags_from_name(values[0].text().to_owned());
+                        }
+                        PopupData::Form(FormKind::NumberTracks, values) => {
+                            self.preview_track_numbers(
+                                values[0].text(),
+                                values[1].toggle(),
+                                values[2].text(),
+                            );
+                        }
+                        PopupData::Form(FormKind::FindReplace, values) => {
+                            self.preview_find_replace(values);
+                        }
+                        PopupData::Form(FormKind::SortFiles, values) => self.sort_files(values),
+                        PopupData::Form(FormKind::CopyFrames, values) => {
+                            self.copy_tag(values[0].text().to_owned());
+                        }
+                        PopupData::Form(FormKind::Duplicates, values) => {
+                            self.find_duplicates(DuplicateMode::ALL[values[0].choice()]);
+                        }
+                        PopupData::Table(TableKind::Duplicates, Some(row)) => {
+                            self.remove_duplicates(row);
+                        }
+                        PopupData::Table(TableKind::Lint, Some(row)) => self.apply_lint_fix(row),
+                        PopupData::Table(TableKind::Preview, _) => {
+                            let removals = std::mem::take(&mut self.pending_removals);
+                            self.remove_entry_frames(removals);
+                            let changes = std::mem::take(&mut self.pending_frames);
+                            self.update_entries(changes);
+                        }
+                        _ => {}
                     }
                 }
                 AppEvent::PopupHelp(help_type) => {
@@ -278,24 +515,51 @@ impl MainState {
                 )),
                 Action::Prev => self.prev(),
                 Action::Next => self.next(),
-                Action::SwitchFocus => self.switch_focus(),
+                Action::SwitchFocus if !self.table_view => self.switch_focus(),
                 Action::RemoveFiles => self.remove_all_files(),
-                Action::WriteTags => self.write_tags().expect("Could not write tags"),
-                Action::SelectCurrent => match self.focus {
-                    Focus::Files => self.select_entry(),
-                    _ => {}
-                },
-                Action::SelectAll => match self.focus {
-                    Focus::Files => self.select_all_entries(),
-                    _ => {}
-                },
+                Action::WriteTags => self.write_tags(),
+                Action::SelectCurrent if self.focus == Focus::Files => self.select_entry(),
+                Action::SelectAll if self.focus == Focus::Files => self.select_all_entries(),
                 Action::Remove => match self.focus {
                     Focus::Files => self.remove_files(),
                     Focus::Details => self.remove_frames(),
                 },
+                Action::SpawnPopup if self.table_view => self.spawn_cell_popup(),
                 Action::SpawnPopup => self.spawn_popup(),
                 Action::UpdateNames => self.update_filenames(),
                 Action::TemplatePopup => self.spawn_template_popup(),
+                Action::TagsFromName => self.spawn_tags_from_name_popup(),
+                Action::NumberTracks => self.spawn_number_tracks_popup(),
+                Action::FindReplace => self.spawn_find_replace_popup(),
+                Action::TitleCase => self.transform_frames(Transform::TitleCase),
+                Action::SentenceCase => self.transform_frames(Transform::SentenceCase),
+                Action::UpperCase => self.transform_frames(Transform::UpperCase),
+                Action::LowerCase => self.transform_frames(Transform::LowerCase),
+                Action::TrimWhitespace => self.transform_frames(Transform::Trim),
+                Action::CollapseSpaces => self.transform_frames(Transform::CollapseSpaces),
+                Action::NormaliseUnicode => self.transform_frames(Transform::Nfc),
+                Action::CopyFrame => self.copy_frame(),
+                Action::CopyTag => self.spawn_copy_tag_popup(),
+                Action::PasteMerge => self.paste_clipboard(false),
+                Action::PasteOverwrite => self.paste_clipboard(true),
+                Action::ToggleTableView => self.toggle_table_view(),
+                Action::ColumnPrev if self.table_view => {
+                    self.table_column = util::prev(self.table_column, self.table_columns.len())
+                }
+                Action::ColumnNext if self.table_view => {
+                    self.table_column = util::next(self.table_column, self.table_columns.len())
+                }
+                Action::SortColumn if self.table_view => self.sort_by_column(),
+                Action::SortFiles => self.spawn_sort_popup(),
+                Action::Search => self.open_search(SearchMode::Search),
+                Action::FilterFiles => self.open_search(SearchMode::Filter),
+                Action::SearchNext => self.search_next(true),
+                Action::SearchPrev => self.search_next(false),
+                Action::StreamInfo => self.spawn_stream_info_popup(),
+                Action::Verify => self.verify_files(),
+                Action::ReplayGain => self.analyse_replaygain(),
+                Action::FindDuplicates => self.spawn_duplicates_popup(),
+                Action::LintAlbum => self.lint_album(true),
                 _ => {}
             }
         }
@@ -309,11 +573,11 @@ impl MainState {
     pub fn next(&mut self) {
         match self.focus {
             Focus::Files => {
-                if self.files.is_empty() {
+                if self.visible.is_empty() {
                     return;
                 }
                 let i = match self.files_state.selected() {
-                    Some(i) => util::next(i, self.files.len()),
+                    Some(i) => util::next(i, self.visible.len()),
                     None => 0,
                 };
                 self.files_state.select(Some(i));
@@ -335,11 +599,11 @@ impl MainState {
     pub fn prev(&mut self) {
         match self.focus {
             Focus::Files => {
-                if self.files.is_empty() {
+                if self.visible.is_empty() {
                     return;
                 }
                 let i = match self.files_state.selected() {
-                    Some(i) => util::prev(i, self.files.len()),
+                    Some(i) => util::prev(i, self.visible.len()),
                     None => 0,
                 };
                 self.files_state.select(Some(i));
@@ -358,23 +622,82 @@ impl MainState {
         }
     }
 
-    // Add files from AppEvent::AddFiles(files)
-    pub fn add_files(&mut self, files: Vec<Entry>) {
-        'outer: for new_entry in files.iter() {
-            for entry in self.files.iter() {
+    // Add files from AppEvent::AddFiles(files), returns the paths of the added files
+    // whose tags still need to be loaded
+    pub fn add_files(&mut self, files: Vec<Entry>) -> Vec<PathBuf> {
+        let mut loading = vec![];
+        'outer: for new_entry in files.into_iter() {
+            for entry in &self.files {
                 if entry.path == new_entry.path {
                     warn!("Duplicate path");
                     continue 'outer;
                 }
             }
-            // TODO - remove this clone
-            self.files.push(new_entry.clone());
+            if new_entry.loading {
+                loading.push(new_entry.path.clone());
+            }
+            self.files.push(new_entry);
+        }
+        self.update_visible(self.highlighted_path());
+        loading
+    }
+
+    // Fill in the tag of a file added while its tag was read in the background. Frames
+    // added to the file in the meantime are kept, a file whose tag could not be read
+    // is removed.
+    pub fn finish_loading(&mut self, path: PathBuf, tag: Option<Tag>, info: Option<StreamInfo>) {
+        let i = match self.files.iter().position(|e| e.path == path) {
+            Some(i) => i,
+            // The file was removed before it finished loading
+            None => return,
+        };
+
+        match tag {
+            Some(mut tag) => {
+                let entry = &mut self.files[i];
+                for frame in entry.tag.frames() {
+                    tag.add_frame(frame.clone());
+                }
+                entry.tag = tag;
+                entry.info = info;
+                entry.loading = false;
+                if self.filter_query.is_some() {
+                    // The file may match the filter now that its tag is known
+                    self.update_visible(self.highlighted_path());
+                } else if self.target_indices().contains(&i) {
+                    self.update_details();
+                }
+            }
+            None => {
+                self.failed_files.push(path);
+                self.remove_entry(i);
+            }
+        }
+    }
+
+    // Remove a file whose tag was never read because its load was cancelled
+    pub fn cancel_loading(&mut self, path: PathBuf) {
+        if let Some(i) = self.files.iter().position(|e| e.path == path && e.loading) {
+            self.remove_entry(i);
         }
     }
 
+    fn remove_entry(&mut self, i: usize) {
+        let highlighted = self.highlighted_path();
+        self.files.remove(i);
+        self.update_visible(highlighted);
+    }
+
+    pub fn loading_count(&self) -> usize {
+        self.files.iter().filter(|e| e.loading).count()
+    }
+
     // Remove all files
     fn remove_all_files(&mut self) {
         self.files.clear();
+        self.visible.clear();
+        self.filter = None;
+        self.filter_query = None;
         self.details.clear();
         self.files_state = ListState::default();
         self.details_state = ListState::default();
@@ -382,92 +705,171 @@ impl MainState {
 
     // Remove all selected and highlighted files
     fn remove_files(&mut self) {
-        let highlighted_filename = if let Some(i) = self.files_state.selected() {
-            self.files[i].filename.clone()
-        } else {
-            "".to_owned() // Filename will never be empty so empty string will never be matched
-        };
-
-        self.files = self
-            .files
-            .iter()
-            .filter(|file| !file.selected)
-            .filter(|file| file.filename != highlighted_filename)
-            .map(|file| file.clone())
-            .collect();
+        let targets = self.target_indices();
+        let mut i = 0;
+        self.files.retain(|_| {
+            i += 1;
+            !targets.contains(&(i - 1))
+        });
 
         if self.files.is_empty() {
+            self.visible.clear();
             self.details.clear();
             self.files_state = ListState::default();
             self.details_state = ListState::default();
         } else {
-            // Check old `files_state` isn't referring to an index outside `files` new length
-            if let Some(i) = self.files_state.selected() {
-                if self.files.len() < i {
-                    self.files_state.select(Some(0));
-                }
-            }
-            self.update_details();
+            // The file after the highlighted one takes its place
+            self.update_visible(None);
         }
     }
 
     fn update_files(&mut self, new_frame: Frame) {
-        for file in &mut self.files {
-            if file.selected {
-                file.tag.add_frame(new_frame.clone());
-            }
+        for i in self.loaded_targets("editing") {
+            self.files[i].tag.add_frame(new_frame.clone());
+        }
+        self.update_details();
+        // Moving on is only useful when editing files one at a time, the merged
+        // details of a selection stay highlighted
+        if self.target_indices().len() == 1 {
+            self.next();
         }
-        if let Some(i) = self.files_state.selected() {
-            self.files[i].tag.add_frame(new_frame);
+    }
+
+    // Add frames to individual files, each item in `changes` holds the path of a
+    // file and the frames to add to it. Files removed in the meantime are skipped.
+    fn update_entries(&mut self, changes: Vec<(PathBuf, Vec<Frame>)>) {
+        let mut changes: HashMap<PathBuf, Vec<Frame>> = changes.into_iter().collect();
+        for entry in &mut self.files {
+            for frame in changes.remove(&entry.path).unwrap_or_default() {
+                entry.tag.add_frame(frame);
+            }
         }
         self.update_details();
-        self.next();
     }
 
-    fn remove_old_txxx_frame(&mut self, description: &str) {
-        for file in &mut self.files {
-            if file.selected {
-                file.tag.remove_extended_text(Some(&description), None);
+    // Remove frames from individual files, each item in `removals` holds the path of a
+    // file and the ids of the frames to remove from it
+    fn remove_entry_frames(&mut self, removals: Vec<(PathBuf, Vec<String>)>) {
+        let mut removals: HashMap<PathBuf, Vec<String>> = removals.into_iter().collect();
+        for entry in &mut self.files {
+            for id in removals.remove(&entry.path).unwrap_or_default() {
+                entry.tag.remove(&id);
+            }
+        }
+    }
+
+    // Forget the changes of the last preview before building a new one
+    fn clear_pending(&mut self) {
+        self.pending_frames.clear();
+        self.pending_removals.clear();
+    }
+
+    // Indices of all selected files and the highlighted file, files hidden by the
+    // filter are left out
+    fn target_indices(&self) -> Vec<usize> {
+        let highlighted = self.highlighted();
+        self.visible
+            .iter()
+            .copied()
+            .filter(|i| self.files[*i].selected || highlighted == Some(*i))
+            .collect()
+    }
+
+    // The targets whose tag has been read. A file that is still loading shows an empty
+    // tag that is merged with its real tag once read, so frames removed or a rename
+    // made in the meantime would be lost. Such files are left out with a warning.
+    fn loaded_targets(&mut self, action: &str) -> Vec<usize> {
+        let mut targets = self.target_indices();
+        let count = targets.len();
+        targets.retain(|i| {
+            let entry = &self.files[*i];
+            if entry.loading {
+                warn!("{} - still loading, not {}", entry.filename, action);
             }
+            !entry.loading
+        });
+        if targets.len() < count {
+            self.trigger_logs = true;
         }
-        if let Some(i) = self.files_state.selected() {
+        targets
+    }
+
+    // Index in `files` of the highlighted file
+    fn highlighted(&self) -> Option<usize> {
+        self.files_state
+            .selected()
+            .and_then(|i| self.visible.get(i).copied())
+    }
+
+    fn highlighted_path(&self) -> Option<PathBuf> {
+        self.highlighted().map(|i| self.files[i].path.clone())
+    }
+
+    // Files shown in the files list, in order
+    pub fn visible_files(&self) -> impl Iterator<Item = &Entry> {
+        self.visible.iter().map(|i| &self.files[*i])
+    }
+
+    fn remove_old_txxx_frame(&mut self, description: &str) {
+        // Files still loading are skipped, and warned about, by the edit that follows
+        for i in self.target_indices() {
+            if self.files[i].loading {
+                continue;
+            }
             self.files[i]
                 .tag
-                .remove_extended_text(Some(&description), None);
+                .remove_extended_text(Some(description), None);
         }
     }
 
     // Toggle selection of highlighted entry
     fn select_entry(&mut self) {
-        match self.files_state.selected() {
-            Some(i) => {
-                self.files[i].selected = !self.files[i].selected;
-                info!(
-                    "{:?} selected: {}",
-                    self.files[i].path, self.files[i].selected
-                );
-            }
-            None => {}
+        if let Some(i) = self.highlighted() {
+            self.files[i].selected = !self.files[i].selected;
+            info!(
+                "{:?} selected: {}",
+                self.files[i].path, self.files[i].selected
+            );
+            self.update_details();
         }
     }
 
     // Select all files in list, if all are already selected then deselect them
     fn select_all_entries(&mut self) {
         let mut any_unselected = false;
-        for entry in &self.files {
-            if !entry.selected {
+        for i in &self.visible {
+            if !self.files[*i].selected {
                 any_unselected = true;
                 break;
             }
         }
-        for entry in &mut self.files {
-            entry.selected = any_unselected;
+        for i in &self.visible {
+            self.files[*i].selected = any_unselected;
         }
         info!("All selected: {}", any_unselected);
+        if self.files_state.selected().is_some() {
+            self.update_details();
+        }
+    }
+
+    fn is_hidden(&self, frame: &Frame) -> bool {
+        self.hidden_frames.iter().any(|id| id == frame.id())
+    }
+
+    // Position of an item in the configured details order, the filename is always
+    // first and unlisted frames go at the end
+    fn details_position(&self, item: &DetailItem) -> usize {
+        match item.frame() {
+            None => 0,
+            Some(frame) => match self.details_order.iter().position(|id| id == frame.id()) {
+                Some(i) => i + 1,
+                None => self.details_order.len() + 1,
+            },
+        }
     }
 
     fn update_details(&mut self) {
-        let index = match self.files_state.selected() {
+        let index = match self.highlighted() {
             Some(i) => i,
             None => {
                 warn!("files_state not selected");
@@ -478,26 +880,59 @@ impl MainState {
 
         let file_name = self.files[index].filename.clone();
         let mut new_details = vec![DetailItem::FileName(file_name)];
-        for frame in self.files[index].tag.frames() {
-            // Only handle text frames
-            if frame.id().starts_with("T") {
-                new_details.push(DetailItem::Frame(frame.clone()));
+        let targets = self.target_indices();
+        if targets.len() > 1 {
+            // Merge the frames of all selected files, a frame only shows a value if
+            // every file has the frame with the same value
+            let mut frames: Vec<&Frame> = vec![];
+            for i in &targets {
+                for frame in self.files[*i].tag.frames() {
+                    if frame.id().starts_with('T') && !frames.iter().any(|f| same_frame(f, frame)) {
+                        frames.push(frame);
+                    }
+                }
+            }
+            for frame in frames.into_iter().filter(|f| !self.is_hidden(f)) {
+                let shared = targets.iter().all(|i| {
+                    self.files[*i]
+                        .tag
+                        .frames()
+                        .any(|f| same_frame(f, frame) && f.content() == frame.content())
+                });
+                new_details.push(match shared {
+                    true => DetailItem::Frame(frame.clone()),
+                    false => DetailItem::Multiple(frame.clone()),
+                });
+            }
+            self.merged_count = targets.len();
+        } else {
+            for frame in self.files[index].tag.frames() {
+                // Only handle text frames
+                if frame.id().starts_with('T') && !self.is_hidden(frame) {
+                    new_details.push(DetailItem::Frame(frame.clone()));
+                }
             }
+            self.merged_count = 0;
         }
-        // TODO - Customise this sort
-        new_details.sort();
+        // Frames listed in `details_order` come first in that order, the rest are
+        // sorted by name
+        new_details.sort_by(|a, b| {
+            self.details_position(a)
+                .cmp(&self.details_position(b))
+                .then_with(|| a.cmp(b))
+        });
         self.details = new_details;
 
         // Check old `details_state` isn't referring to an index outside `new_details` length
         if let Some(i) = self.details_state.selected() {
-            if self.details.len() < i {
+            if self.details.len() <= i {
                 self.details_state.select(Some(0));
             }
         }
     }
 
     fn update_filename(&mut self, name: String) {
-        let index = match self.files_state.selected() {
+        let index = match self.highlighted() {
             Some(i) => i,
             None => {
                 warn!("files_state not selected");
@@ -506,7 +941,15 @@ impl MainState {
             }
         };
 
-        self.files[index].filename = name;
+        let entry = &mut self.files[index];
+        if entry.loading {
+            warn!("{} - still loading, not renaming", entry.filename);
+            self.trigger_logs = true;
+            // Show the old name again
+            self.update_details();
+            return;
+        }
+        entry.filename = name;
     }
 
     fn switch_focus(&mut self) {
@@ -514,7 +957,7 @@ impl MainState {
             Focus::Files => {
                 // On switching focus to the details list for the first time
                 // select the first item
-                if self.details_state.selected() == None {
+                if self.details_state.selected().is_none() {
                     self.details_state.select(Some(0));
                 }
                 self.focus = Focus::Details
@@ -536,17 +979,15 @@ impl MainState {
             _ => Frame::text(id, ""),
         };
 
-        let highlighted = self.files_state.selected();
-        for (i, entry) in self.files.iter_mut().enumerate() {
-            if entry.selected || highlighted.is_some() && highlighted.unwrap() == i {
-                if frame.id() != "TXXX" {
-                    if entry.tag.get(frame.id()).is_none() {
-                        entry.tag.add_frame(frame.clone());
-                    }
-                } else {
-                    // Multiple TXXX frames allowed so add a new one even if one already exists
+        for i in self.loaded_targets("adding frames") {
+            let entry = &mut self.files[i];
+            if frame.id() != "TXXX" {
+                if entry.tag.get(frame.id()).is_none() {
                     entry.tag.add_frame(frame.clone());
                 }
+            } else {
+                // Multiple TXXX frames allowed so add a new one even if one already exists
+                entry.tag.add_frame(frame.clone());
             }
         }
 
@@ -561,7 +1002,7 @@ impl MainState {
                     warn!("Not a frame");
                     return;
                 }
-                DetailItem::Frame(frame) => match frame.id() {
+                DetailItem::Frame(frame) | DetailItem::Multiple(frame) => match frame.id() {
                     "TXXX" => (
                         frame.id(),
                         Some(
@@ -579,15 +1020,14 @@ impl MainState {
         } else {
             unreachable!();
         };
+        let (id, desc) = (id.to_owned(), desc.map(|d| d.to_owned()));
 
-        let highlighted = self.files_state.selected();
-        for (i, file) in &mut self.files.iter_mut().enumerate() {
-            if file.selected || highlighted.is_some() && highlighted.unwrap() == i {
-                if id == "TXXX" {
-                    file.tag.remove_extended_text(desc, None);
-                } else {
-                    file.tag.remove(id);
-                }
+        for i in self.loaded_targets("removing frames") {
+            let file = &mut self.files[i];
+            if id == "TXXX" {
+                file.tag.remove_extended_text(desc.as_deref(), None);
+            } else {
+                file.tag.remove(&id);
             }
         }
 
@@ -598,7 +1038,7 @@ impl MainState {
         if let Some(i) = self.details_state.selected() {
             match &self.details[i] {
                 DetailItem::FileName(file_name) => {
-                    let popup = SingleInput::new("Filename", &file_name);
+                    let popup = SingleInput::new("Filename", file_name);
                     self.popup_stack.push(Box::new(popup));
                 }
                 DetailItem::Frame(frame) => match frame.id() {
@@ -622,6 +1062,17 @@ impl MainState {
                         self.trigger_logs = true;
                     }
                 },
+                // The files have different values so start with an empty value
+                DetailItem::Multiple(frame) => match frame.content().extended_text() {
+                    Some(text) => {
+                        let popup = DoubleInput::new(&text.description, "");
+                        self.popup_stack.push(Box::new(popup));
+                    }
+                    None => {
+                        let popup = SingleInput::new(frame.id(), "");
+                        self.popup_stack.push(Box::new(popup));
+                    }
+                },
             }
         }
     }
@@ -631,146 +1082,1394 @@ impl MainState {
         self.popup_stack.push(Box::new(popup));
     }
 
-    // Write updated tags to files
-    fn write_tags(&mut self) -> Result<(), anyhow::Error> {
-        info!("Writing tags to files...");
-        for entry in self.files.iter_mut() {
-            // Check for empty tags and frames before writing to the file, currently
-            // this will write nothing to the file even if there are valid frames to
-            // write and only one error is found
-            let mut abort_write = false;
-            for frame in entry.tag.frames() {
-                match frame.content() {
-                    Content::Text(t) => {
-                        if t.is_empty() {
-                            error!(
-                                "{} - {} frame empty, not writing tag",
-                                entry.filename,
-                                frame.id()
-                            );
-                            self.trigger_logs = true;
-                            abort_write = true;
-                        }
-                    }
-                    Content::ExtendedText(ExtendedText { description, value }) => {
-                        if description.is_empty() || value.is_empty() {
-                            error!(
-                                "{} - {} frame empty, not writing tag",
-                                entry.filename,
-                                frame.id()
-                            );
-                            self.trigger_logs = true;
-                            abort_write = true;
-                        }
-                    }
-                    _ => {}
+    fn spawn_tags_from_name_popup(&mut self) {
+        let popup = FormPopup::new(
+            "Tags From Filename",
+            FormKind::TagsFromName,
+            vec![FormField::text("Pattern", &self.name_pattern)],
+        );
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Parse frame values from the filenames of the selected files and show them in a
+    // preview popup. The frames are added when the preview is confirmed.
+    fn preview_tags_from_name(&mut self, pattern: String) {
+        let tag_pattern = match TagPattern::new(&pattern, &self.rex) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("{}", e);
+                self.trigger_logs = true;
+                return;
+            }
+        };
+        self.name_pattern = pattern;
+
+        let header = tag_pattern.preview_header();
+        let mut rows = vec![];
+        self.clear_pending();
+        for i in self.target_indices() {
+            let entry = &self.files[i];
+            // Loaded files can come from anywhere so every parent directory is used
+            let subject = tag_pattern
+                .subject(None, &entry.path, &entry.filename)
+                .unwrap_or_else(|| entry.filename.clone());
+            let mut row = vec![subject.clone()];
+            match tag_pattern.captures(&subject) {
+                Some(values) => {
+                    let frames = tag_pattern.frames(&values);
+                    self.pending_frames.push((entry.path.clone(), frames));
+                    row.extend(values);
+                }
+                None => {
+                    warn!("{} does not match pattern", subject);
+                    self.trigger_logs = true;
+                    row.extend(tag_pattern.frame_ids().iter().map(|_| "-".to_owned()));
                 }
             }
+            rows.push(row);
+        }
 
-            if !abort_write && entry.tag.frames().count() != 0 {
-                entry.tag.write_to_path(&entry.path, Version::Id3v24)?;
+        if rows.is_empty() {
+            warn!("No files selected");
+            self.trigger_logs = true;
+            return;
+        }
+
+        let popup = TablePopup::new("Tags From Filename", TableKind::Preview, header, rows);
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    fn spawn_number_tracks_popup(&mut self) {
+        let popup = FormPopup::new(
+            "Number Tracks",
+            FormKind::NumberTracks,
+            vec![
+                FormField::text("Start", "1"),
+                FormField::toggle("Include total (n/total)", false),
+                FormField::text("Disc (leave empty to skip)", ""),
+            ],
+        );
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Number the selected files sequentially in their current list order and show
+    // the new track numbers in a preview popup
+    fn preview_track_numbers(&mut self, start: &str, with_total: bool, disc: &str) {
+        let start = match start.trim().parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                error!("Start track '{}' is not a number", start);
+                self.trigger_logs = true;
+                return;
             }
-            // TODO - There are still some edge cases left. If all frames are removed this
-            //        implies that the user wishes to delete the tag from the file which does
-            //        not happen yet. However it is currently possible for a file to have a
-            //        tag with a frame that is not supported (and is not shown in the UI). In
-            //        this case the tag should not be removed (the user also needs to be made
-            //        aware of what happened)
+        };
+        let disc = disc.trim();
 
-            // Rename the file, for now the extension must be included
-            // when the user enters the new filename
-            let mut new_path = entry.path.clone();
-            new_path.set_file_name(&entry.filename);
-            fs::rename(&entry.path, &new_path)?;
-            entry.path = new_path;
+        let targets = self.target_indices();
+        if targets.is_empty() {
+            warn!("No files selected");
+            self.trigger_logs = true;
+            return;
+        }
+        let total = start + targets.len() - 1;
+
+        let mut rows = vec![];
+        self.clear_pending();
+        for (n, i) in targets.into_iter().enumerate() {
+            let track = match with_total {
+                true => format!("{}/{}", start + n, total),
+                false => (start + n).to_string(),
+            };
+
+            let mut frames = vec![Frame::text("TRCK", track.as_str())];
+            if !disc.is_empty() {
+                frames.push(Frame::text("TPOS", disc));
+            }
+            let entry = &self.files[i];
+            self.pending_frames.push((entry.path.clone(), frames));
+            rows.push(vec![entry.filename.clone(), track, disc.to_owned()]);
         }
 
-        info!("New tags written");
-        Ok(())
+        let header = vec!["Filename".to_owned(), "Track".to_owned(), "Disc".to_owned()];
+        let popup = TablePopup::new("Number Tracks", TableKind::Preview, header, rows);
+        self.popup_stack.push(Box::new(popup));
     }
 
-    // TODO - Format track numbers with a leading zero
-    fn update_filenames(&mut self) {
-        let mats: Vec<Match> = self.rex.find_iter(&self.template_string).collect();
-
-        let mut frame_ids = vec![];
-        for mat in &mats {
-            let text: Vec<&str> = mat.as_str().split(&['{', '}']).collect();
+    fn spawn_find_replace_popup(&mut self) {
+        let values = &self.find_replace_values;
+        let popup = FormPopup::new(
+            "Find And Replace",
+            FormKind::FindReplace,
+            vec![
+                FormField::text("Find", values[0].text()),
+                FormField::text("Replace", values[1].text()),
+                FormField::toggle("Regex", values[2].toggle()),
+                FormField::toggle("Case sensitive", values[3].toggle()),
+                FormField::text("Frames (leave empty for all)", values[4].text()),
+            ],
+        );
+        self.popup_stack.push(Box::new(popup));
+    }
 
-            if let Ok(id) = frame_data::name_to_id(text[1]) {
-                frame_ids.push(id);
-            } else {
-                error!("Unknown frame id in template string: '{}'", text[1]);
+    // Find every match in the frames of the selected files and show the replaced
+    // values in a preview popup
+    fn preview_find_replace(&mut self, values: Vec<FormValue>) {
+        let find_replace = match FindReplace::new(
+            values[0].text(),
+            values[1].text(),
+            values[2].toggle(),
+            values[3].toggle(),
+            values[4].text(),
+        ) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("{}", e);
                 self.trigger_logs = true;
                 return;
             }
+        };
+        self.find_replace_values = values;
+
+        let mut rows = vec![];
+        let mut matches = 0;
+        self.clear_pending();
+        for i in self.target_indices() {
+            let entry = &self.files[i];
+            let mut frames = vec![];
+            for frame in entry.tag.frames() {
+                if let Some((new_frame, count)) = find_replace.apply(frame) {
+                    rows.push(vec![
+                        entry.filename.clone(),
+                        frame.name().to_owned(),
+                        frame.content().to_string(),
+                        new_frame.content().to_string(),
+                    ]);
+                    matches += count;
+                    frames.push(new_frame);
+                }
+            }
+            if !frames.is_empty() {
+                self.pending_frames.push((entry.path.clone(), frames));
+            }
         }
 
-        'entries: for entry in self.files.iter_mut() {
-            if entry.selected {
-                let mut contents = vec![];
-                for id in &frame_ids {
-                    if let Some(frame) = entry.tag.get(id) {
-                        match frame.content() {
-                            Content::Text(text) => contents.push(text),
-                            _ => {
-                                error!("Content type not supported");
-                                self.trigger_logs = true;
-                                continue 'entries;
-                            }
-                        }
-                    } else {
-                        error!("{} does not contain {} frame", entry.filename, id);
-                        self.trigger_logs = true;
-                        continue 'entries;
-                    }
+        if rows.is_empty() {
+            info!("No matches found");
+            self.trigger_logs = true;
+            return;
+        }
+
+        let title = format!(
+            "Find And Replace - {} matches in {} files",
+            matches,
+            self.pending_frames.len()
+        );
+        let header = vec![
+            "Filename".to_owned(),
+            "Frame".to_owned(),
+            "Before".to_owned(),
+            "After".to_owned(),
+        ];
+        let popup = TablePopup::new(&title, TableKind::Preview, header, rows);
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Show the result of a text transform on frames of the selected files in a preview
+    // popup. With the details list focused only the highlighted frame is changed,
+    // otherwise the frames set in the `transform_frames` config value (or all text
+    // frames if it is empty).
+    fn transform_frames(&mut self, transform: Transform) {
+        // TXXX frames are identified by their description as well as their id
+        let (frame_ids, description) = match self.focus {
+            Focus::Details => match self
+                .details_state
+                .selected()
+                .and_then(|i| self.details[i].frame())
+            {
+                Some(frame) => (
+                    vec![frame.id().to_owned()],
+                    frame
+                        .content()
+                        .extended_text()
+                        .map(|t| t.description.clone()),
+                ),
+                _ => {
+                    warn!("Not a frame");
+                    self.trigger_logs = true;
+                    return;
                 }
+            },
+            Focus::Files => (self.transform_frames.clone(), None),
+        };
 
-                let mut new_name = self.template_string.clone();
-                for (i, mat) in mats.iter().enumerate() {
-                    let content = if frame_ids[i] == "TRCK" {
-                        // Add leading zero to track number
-                        // TODO - Handle number/total format
-                        if contents[i].len() < 2 {
-                            format!("0{}", contents[i])
-                        } else {
-                            contents[i].clone()
+        let mut rows = vec![];
+        self.clear_pending();
+        for i in self.target_indices() {
+            let entry = &self.files[i];
+            let mut frames = vec![];
+            for frame in entry.tag.frames() {
+                if !frame_ids.is_empty() && !frame_ids.iter().any(|id| id == frame.id()) {
+                    continue;
+                }
+                let content = match frame.content() {
+                    Content::Text(text) => {
+                        Content::Text(transform.apply(text, &self.title_case_exceptions))
+                    }
+                    Content::ExtendedText(text) => {
+                        if description.is_some() && description.as_ref() != Some(&text.description)
+                        {
+                            continue;
                         }
-                    } else {
-                        contents[i].clone()
-                    };
-
-                    new_name = new_name.replace(mat.as_str(), &content);
+                        Content::ExtendedText(ExtendedText {
+                            description: text.description.clone(),
+                            value: transform.apply(&text.value, &self.title_case_exceptions),
+                        })
+                    }
+                    _ => continue,
+                };
+                if &content != frame.content() {
+                    rows.push(vec![
+                        entry.filename.clone(),
+                        frame.name().to_owned(),
+                        frame.content().to_string(),
+                        content.to_string(),
+                    ]);
+                    frames.push(Frame::with_content(frame.id(), content));
                 }
-                entry.filename = new_name;
+            }
+            if !frames.is_empty() {
+                self.pending_frames.push((entry.path.clone(), frames));
             }
         }
 
-        self.update_details();
+        if rows.is_empty() {
+            info!("{} - no frames changed", transform.name());
+            self.trigger_logs = true;
+            return;
+        }
+
+        let title = format!("{} - {} files", transform.name(), self.pending_frames.len());
+        let header = vec![
+            "Filename".to_owned(),
+            "Frame".to_owned(),
+            "Before".to_owned(),
+            "After".to_owned(),
+        ];
+        let popup = TablePopup::new(&title, TableKind::Preview, header, rows);
+        self.popup_stack.push(Box::new(popup));
     }
 
-    pub fn popup_widget(&self) -> Option<&Box<dyn Popup>> {
-        self.popup_stack.last()
+    // Copy the highlighted frame in the details list to the clipboard
+    fn copy_frame(&mut self) {
+        let frame = match self.details_state.selected().map(|i| &self.details[i]) {
+            Some(DetailItem::Frame(frame)) if self.focus == Focus::Details => frame.clone(),
+            Some(DetailItem::Multiple(_)) if self.focus == Focus::Details => {
+                warn!("Frame has multiple values, not copying");
+                self.trigger_logs = true;
+                return;
+            }
+            _ => {
+                warn!("No frame highlighted");
+                self.trigger_logs = true;
+                return;
+            }
+        };
+
+        info!("Copied {} frame", frame.id());
+        self.clipboard = vec![frame];
     }
 
-    pub fn spawn_help_popup(&mut self, help_popup: HelpPopup) {
-        self.popup_stack.push(Box::new(help_popup));
+    fn spawn_copy_tag_popup(&mut self) {
+        let popup = FormPopup::new(
+            "Copy Frames",
+            FormKind::CopyFrames,
+            vec![FormField::text(
+                "Frames (leave empty for the whole tag)",
+                &self.copy_frames,
+            )],
+        );
+        self.popup_stack.push(Box::new(popup));
     }
 
-    pub fn update_help_text(&mut self, config: &Config) {
-        let quit = config.get_key(&Action::Quit).unwrap();
-        let remove_all = config.get_key(&Action::RemoveFiles).unwrap();
-        let remove = config.get_key(&Action::Remove).unwrap();
-        let select_all = config.get_key(&Action::SelectAll).unwrap();
-        let select = config.get_key(&Action::SelectCurrent).unwrap();
-        let write = config.get_key(&Action::WriteTags).unwrap();
-        let template = config.get_key(&Action::TemplatePopup).unwrap();
-        let update = config.get_key(&Action::UpdateNames).unwrap();
+    // Copy the frames listed in `frames`, or every frame if it is empty, from the
+    // highlighted file to the clipboard
+    fn copy_tag(&mut self, frames: String) {
+        let index = match self.highlighted() {
+            Some(i) => i,
+            None => {
+                warn!("files_state not selected");
+                self.trigger_logs = true;
+                return;
+            }
+        };
 
-        self.help_text = vec![
-            format!("`{}` - Quit", util::display_keycode(quit)),
-            format!("`{}` - Remove all files", util::display_keycode(remove_all)),
-            format!(
+        let frame_ids = parse_frame_ids(&frames);
+        self.clipboard = self.files[index]
+            .tag
+            .frames()
+            .filter(|f| frame_ids.is_empty() || frame_ids.iter().any(|id| id == f.id()))
+            .cloned()
+            .collect();
+        self.copy_frames = frames;
+
+        info!(
+            "Copied {} frames from {}",
+            self.clipboard.len(),
+            self.files[index].filename
+        );
+    }
+
+    // Show the frames that pasting the clipboard changes in the selected files in a
+    // preview popup. When `overwrite` is true every other text frame is removed as
+    // well, otherwise only frames with the same id are replaced. Pictures, comments
+    // and other frames that aren't text are always kept.
+    fn paste_clipboard(&mut self, overwrite: bool) {
+        if self.clipboard.is_empty() {
+            warn!("Clipboard is empty");
+            self.trigger_logs = true;
+            return;
+        }
+
+        let mut rows = vec![];
+        self.clear_pending();
+        for i in self.target_indices() {
+            let entry = &self.files[i];
+            // The tag read in the background would be merged over the pasted frames
+            if entry.loading {
+                warn!("{} - still loading, not pasting", entry.filename);
+                self.trigger_logs = true;
+                continue;
+            }
+
+            let mut removed = vec![];
+            for frame in entry.tag.frames() {
+                if !overwrite || !frame.id().starts_with('T') {
+                    continue;
+                }
+                if !self.clipboard.iter().any(|f| same_frame(f, frame)) {
+                    rows.push(vec![
+                        entry.filename.clone(),
+                        frame.name().to_owned(),
+                        frame.content().to_string(),
+                        "-".to_owned(),
+                    ]);
+                }
+                if !removed.iter().any(|id| id == frame.id()) {
+                    removed.push(frame.id().to_owned());
+                }
+            }
+            for frame in &self.clipboard {
+                let before = entry.tag.frames().find(|f| same_frame(f, frame));
+                rows.push(vec![
+                    entry.filename.clone(),
+                    frame.name().to_owned(),
+                    before.map_or("-".to_owned(), |f| f.content().to_string()),
+                    frame.content().to_string(),
+                ]);
+            }
+            self.pending_removals.push((entry.path.clone(), removed));
+            self.pending_frames
+                .push((entry.path.clone(), self.clipboard.clone()));
+        }
+
+        if self.pending_frames.is_empty() {
+            warn!("No files to paste into");
+            self.trigger_logs = true;
+            return;
+        }
+
+        let title = format!(
+            "Paste {} Frames - {} files",
+            self.clipboard.len(),
+            self.pending_frames.len()
+        );
+        let header = vec![
+            "Filename".to_owned(),
+            "Frame".to_owned(),
+            "Before".to_owned(),
+            "After".to_owned(),
+        ];
+        let popup = TablePopup::new(&title, TableKind::Preview, header, rows);
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Title of the files list showing the search line, the filter and the number of
+    // files still loading
+    pub fn files_title(&self) -> String {
+        let title = match (&self.search_input, self.search_mode) {
+            (Some(text), SearchMode::Search) => format!("Files - /{}", text),
+            (Some(text), SearchMode::Filter) => format!("Files - Filter: {}", text),
+            (None, _) => match &self.filter {
+                Some(filter) => format!(
+                    "Files - {} of {} match '{}'",
+                    self.visible.len(),
+                    self.files.len(),
+                    filter
+                ),
+                None => "Files".to_owned(),
+            },
+        };
+
+        match self.loading_count() {
+            0 => title,
+            n => format!("{} - Loading {}", title, n),
+        }
+    }
+
+    fn open_search(&mut self, mode: SearchMode) {
+        self.search_mode = mode;
+        self.search_origin = self.highlighted();
+        self.search_input = Some(match mode {
+            SearchMode::Search => "".to_owned(),
+            SearchMode::Filter => self.filter.clone().unwrap_or_default(),
+        });
+        self.focus = Focus::Files;
+    }
+
+    // Keys typed while the search line is open, every change updates the
+    // highlighted file or the filter straight away
+    fn handle_search_input(&mut self, key: &KeyEvent) {
+        let mut text = self.search_input.take().unwrap_or_default();
+        match key.code {
+            KeyCode::Esc => {
+                match self.search_mode {
+                    SearchMode::Search => self.highlight(self.search_origin),
+                    SearchMode::Filter => {
+                        let filter = self.filter.clone().unwrap_or_default();
+                        self.apply_filter(&filter);
+                    }
+                }
+                return;
+            }
+            KeyCode::Enter => {
+                self.commit_search(&text);
+                return;
+            }
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) => text.push(c),
+            _ => {}
+        }
+
+        match self.search_mode {
+            SearchMode::Search => match FileQuery::parse(&text) {
+                Ok(query) if !query.is_empty() => {
+                    let start = self
+                        .search_origin
+                        .and_then(|i| self.visible.iter().position(|v| *v == i))
+                        .unwrap_or(0);
+                    if let Some(i) = self.find_match(&query, start, true) {
+                        self.highlight(Some(i));
+                    }
+                }
+                Ok(_) => self.highlight(self.search_origin),
+                // The query is probably still being typed, e.g. an unclosed quote
+                Err(_) => {}
+            },
+            SearchMode::Filter => self.apply_filter(&text),
+        }
+        self.search_input = Some(text);
+    }
+
+    fn commit_search(&mut self, text: &str) {
+        let query = match FileQuery::parse(text) {
+            Ok(query) => query,
+            Err(e) => {
+                error!("{}", e);
+                self.trigger_logs = true;
+                return;
+            }
+        };
+
+        match self.search_mode {
+            SearchMode::Search => {
+                if query.is_empty() {
+                    return;
+                }
+                if !self.visible_files().any(|e| query.matches(e)) {
+                    warn!("No files match '{}'", text);
+                    self.trigger_logs = true;
+                }
+                self.search_query = Some(query);
+            }
+            SearchMode::Filter => {
+                self.apply_filter(text);
+                self.filter = match query.is_empty() {
+                    true => None,
+                    false => Some(text.to_owned()),
+                };
+                // Searching within the filtered files uses the same query
+                self.search_query = Some(query);
+            }
+        }
+    }
+
+    // Highlight the next or previous file matching the last search
+    fn search_next(&mut self, forward: bool) {
+        let query = match &self.search_query {
+            Some(query) => query.clone(),
+            None => {
+                info!("No search entered");
+                return;
+            }
+        };
+        let start = match (self.files_state.selected(), forward) {
+            (Some(i), true) => i + 1,
+            (Some(i), false) => i + self.visible.len() - 1,
+            (None, _) => 0,
+        };
+        match self.find_match(&query, start, forward) {
+            Some(i) => self.highlight(Some(i)),
+            None => {
+                warn!("No files match search");
+                self.trigger_logs = true;
+            }
+        }
+    }
+
+    // Index in `files` of the first shown file matching from position `start` in the
+    // files list, wrapping around the list
+    fn find_match(&self, query: &FileQuery, start: usize, forward: bool) -> Option<usize> {
+        let len = self.visible.len();
+        (0..len)
+            .map(|k| match forward {
+                true => (start + k) % len,
+                false => (start + len * 2 - k) % len,
+            })
+            .map(|position| self.visible[position])
+            .find(|i| query.matches(&self.files[*i]))
+    }
+
+    // Highlight the file at `index` in `files`, nothing is highlighted if it is hidden
+    fn highlight(&mut self, index: Option<usize>) {
+        let position = index.and_then(|i| self.visible.iter().position(|v| *v == i));
+        self.select_position(position);
+    }
+
+    // Highlight the file at `position` in the files list
+    fn select_position(&mut self, position: Option<usize>) {
+        let position = position.filter(|p| *p < self.visible.len());
+        self.files_state.select(position);
+        match position {
+            Some(_) => self.update_details(),
+            None => {
+                self.details.clear();
+                self.merged_count = 0;
+            }
+        }
+    }
+
+    // Work out which files are shown after the filter or the files list changed. The
+    // file at `highlighted` stays highlighted if it is shown, otherwise the file now
+    // in the same position is.
+    fn update_visible(&mut self, highlighted: Option<PathBuf>) {
+        let query = self.filter_query.as_ref();
+        self.visible = (0..self.files.len())
+            .filter(|i| query.is_none_or(|q| q.matches(&self.files[*i])))
+            .collect();
+
+        let last = self.visible.len().checked_sub(1);
+        let position = highlighted
+            .and_then(|path| {
+                self.visible
+                    .iter()
+                    .position(|i| self.files[*i].path == path)
+            })
+            .or_else(|| Some(self.files_state.selected()?.min(last?)));
+        self.select_position(position);
+    }
+
+    // Hide the files not matching `text`, an empty query shows every file. The
+    // highlighted file stays highlighted if it still matches and hidden files are
+    // deselected.
+    fn apply_filter(&mut self, text: &str) {
+        let query = match FileQuery::parse(text) {
+            Ok(query) => query,
+            Err(_) => return,
+        };
+        self.filter_query = (!query.is_empty()).then_some(query);
+        self.update_visible(self.highlighted_path());
+
+        let mut shown = vec![false; self.files.len()];
+        for i in &self.visible {
+            shown[*i] = true;
+        }
+        for (entry, shown) in self.files.iter_mut().zip(shown) {
+            entry.selected &= shown;
+        }
+    }
+
+    fn toggle_table_view(&mut self) {
+        self.table_view = !self.table_view;
+        self.focus = Focus::Files;
+    }
+
+    // Edit the highlighted cell of the table view
+    fn spawn_cell_popup(&mut self) {
+        let index = match self.highlighted() {
+            Some(i) => i,
+            None => return,
+        };
+
+        let column = &self.table_columns[self.table_column];
+        let popup = match column {
+            Column::FileName => SingleInput::new("Filename", &self.files[index].filename),
+            Column::Frame(id) if id.starts_with('T') && id != "TXXX" => {
+                SingleInput::new(id, &column.value(&self.files[index]))
+            }
+            Column::Frame(id) => {
+                warn!("Unhandled frame type: {}", id);
+                self.trigger_logs = true;
+                return;
+            }
+        };
+        self.table_edit = Some(self.table_column);
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Set the value of a cell in the highlighted row of the table view
+    fn update_cell(&mut self, column: usize, text: String) {
+        let index = match self.highlighted() {
+            Some(i) => i,
+            None => return,
+        };
+
+        match &self.table_columns[column] {
+            Column::FileName => self.update_filename(text),
+            Column::Frame(_) if self.files[index].loading => {
+                warn!(
+                    "{} - still loading, not editing",
+                    self.files[index].filename
+                );
+                self.trigger_logs = true;
+            }
+            Column::Frame(id) => {
+                let frame = Frame::text(id.as_str(), text);
+                let path = self.files[index].path.clone();
+                self.update_entries(vec![(path, vec![frame])]);
+            }
+        }
+    }
+
+    // Sort the files by the highlighted column, sorting by the same column again
+    // reverses the order
+    fn sort_by_column(&mut self) {
+        let descending = match self.table_sort {
+            Some((column, descending)) if column == self.table_column => !descending,
+            _ => false,
+        };
+        self.table_sort = Some((self.table_column, descending));
+
+        let column = self.table_columns[self.table_column].clone();
+        self.sort_entries(descending, |e| SortValue::from_text(&column.value(e)));
+    }
+
+    fn spawn_sort_popup(&mut self) {
+        let options = SortKey::ALL.iter().map(|k| k.name().to_owned()).collect();
+        let popup = FormPopup::new(
+            "Sort Files",
+            FormKind::SortFiles,
+            vec![
+                FormField::choice("Sort by", options, self.sort_values[0].choice()),
+                FormField::toggle("Descending", self.sort_values[1].toggle()),
+            ],
+        );
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    fn sort_files(&mut self, values: Vec<FormValue>) {
+        let key = SortKey::ALL[values[0].choice()];
+        self.sort_entries(values[1].toggle(), |e| key.value(e));
+        // The table no longer matches the order of any column
+        self.table_sort = None;
+        self.sort_values = values;
+    }
+
+    // Sort the files list keeping the same file highlighted, selected files stay
+    // selected. Files without a value are always sorted last and files with equal
+    // values keep their order.
+    fn sort_entries<F>(&mut self, descending: bool, key: F)
+    where
+        F: Fn(&Entry) -> Option<SortValue>,
+    {
+        let highlighted = self.highlighted_path();
+
+        let mut keyed: Vec<(Option<SortValue>, Entry)> =
+            self.files.drain(..).map(|e| (key(&e), e)).collect();
+        keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) if descending => b.cmp(a),
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        self.files = keyed.into_iter().map(|(_, e)| e).collect();
+        self.update_visible(highlighted);
+    }
+
+    pub fn apply_config(&mut self, config: &Config) {
+        self.title_case_exceptions = config.get_title_case_exceptions();
+        self.transform_frames = parse_frame_ids(&config.get_transform_frames().join(","));
+        self.details_order = parse_frame_ids(&config.get_details_order().join(","));
+        self.hidden_frames = parse_frame_ids(&config.get_hidden_frames().join(","));
+        if self.highlighted().is_some() {
+            self.update_details();
+        }
+        self.table_columns = config
+            .get_table_columns()
+            .iter()
+            .map(|c| Column::parse(c))
+            .collect();
+        if self.table_columns.is_empty() {
+            self.table_columns.push(Column::FileName);
+        }
+        if self.table_column >= self.table_columns.len() {
+            self.table_column = 0;
+            self.table_sort = None;
+        }
+        self.validation_rules = Rules::new(config);
+    }
+
+    // Write updated tags to files, files that can't be written are logged and the
+    // rest are still written
+    fn write_tags(&mut self) {
+        info!("Writing tags to files...");
+        let mut violations = vec![];
+        let mut failed = 0;
+        // Files hidden by the filter are written too
+        for entry in self.files.iter_mut() {
+            // Writing the empty tag of a file that is still loading would remove its
+            // existing frames
+            if entry.loading {
+                warn!("{} - still loading, not writing tag", entry.filename);
+                self.trigger_logs = true;
+                continue;
+            }
+
+            // Check for empty tags and frames before writing to the file, currently
+            // this will write nothing to the file even if there are valid frames to
+            // write and only one error is found
+            let mut abort_write = false;
+            for frame in entry.tag.frames() {
+                match frame.content() {
+                    Content::Text(t) if t.is_empty() => {
+                        error!(
+                            "{} - {} frame empty, not writing tag",
+                            entry.filename,
+                            frame.id()
+                        );
+                        self.trigger_logs = true;
+                        abort_write = true;
+                    }
+                    Content::ExtendedText(ExtendedText { description, value })
+                        if (description.is_empty() || value.is_empty()) =>
+                    {
+                        error!(
+                            "{} - {} frame empty, not writing tag",
+                            entry.filename,
+                            frame.id()
+                        );
+                        self.trigger_logs = true;
+                        abort_write = true;
+                    }
+                    _ => {}
+                }
+            }
+            // A file whose tag is not written keeps its old name as well
+            if abort_write {
+                failed += 1;
+                continue;
+            }
+
+            // Only tags that are about to be written are validated, a file that breaks
+            // a rule in block mode is left unchanged including its name
+            let mut blocked = false;
+            if entry.tag.frames().count() != 0 {
+                let block = self.validation_rules.mode == ValidationMode::Block;
+                for problem in self.validation_rules.check(&entry.tag) {
+                    if block {
+                        error!("{} - {}, not writing tag", entry.filename, problem);
+                        blocked = true;
+                    } else {
+                        warn!("{} - {}", entry.filename, problem);
+                    }
+                    self.trigger_logs = true;
+                    violations.push(vec![entry.filename.clone(), problem]);
+                }
+                if blocked {
+                    continue;
+                }
+
+                match backend_for(&entry.path) {
+                    Some(backend) => {
+                        if let Err(e) = backend.write(&entry.tag, &entry.path) {
+                            error!("Failed to write {} - {}", entry.filename, e);
+                            self.trigger_logs = true;
+                            failed += 1;
+                            continue;
+                        }
+                    }
+                    None => {
+                        error!("Failed to write {:?} - unsupported file type", entry.path);
+                        self.trigger_logs = true;
+                        failed += 1;
+                        continue;
+                    }
+                }
+            }
+            // TODO - There are still some edge cases left. If all frames are removed this
+            //        implies that the user wishes to delete the tag from the file which does
+            //        not happen yet. However it is currently possible for a file to have a
+            //        tag with a frame that is not supported (and is not shown in the UI). In
+            //        this case the tag should not be removed (the user also needs to be made
+            //        aware of what happened)
+
+            // Rename the file, for now the extension must be included
+            // when the user enters the new filename
+            let mut new_path = entry.path.clone();
+            new_path.set_file_name(&entry.filename);
+            match fs::rename(&entry.path, &new_path) {
+                Ok(()) => entry.path = new_path,
+                Err(e) => {
+                    error!("Failed to rename {:?} - {}", entry.path, e);
+                    self.trigger_logs = true;
+                    failed += 1;
+                }
+            }
+        }
+
+        if !violations.is_empty() {
+            let title = match self.validation_rules.mode {
+                ValidationMode::Block => "Validation Failed - Tags Not Written",
+                _ => "Validation Warnings",
+            };
+            let header = vec!["File".to_owned(), "Problem".to_owned()];
+            let popup = TablePopup::new(title, TableKind::Info, header, violations);
+            self.popup_stack.push(Box::new(popup));
+        }
+
+        match failed {
+            0 => info!("New tags written"),
+            n => error!("{} files could not be written", n),
+        }
+    }
+
+    // TODO - Format track numbers with a leading zero
+    fn update_filenames(&mut self) {
+        let mats: Vec<Match> = self.rex.find_iter(&self.template_string).collect();
+
+        let mut tokens = vec![];
+        for mat in &mats {
+            let text: Vec<&str> = mat.as_str().split(&['{', '}']).collect();
+
+            if let Ok(id) = frame_data::name_to_id(text[1]) {
+                tokens.push(TemplateToken::Frame(id));
+            } else if mpeg::PROPERTIES.contains(&text[1]) {
+                tokens.push(TemplateToken::Property(text[1]));
+            } else {
+                error!("Unknown frame id in template string: '{}'", text[1]);
+                self.trigger_logs = true;
+                return;
+            }
+        }
+
+        'entries: for i in &self.visible {
+            let entry = &mut self.files[*i];
+            if entry.selected {
+                if entry.loading {
+                    warn!("{} - still loading, not renaming", entry.filename);
+                    self.trigger_logs = true;
+                    continue;
+                }
+                let mut contents = vec![];
+                for token in &tokens {
+                    let id = match token {
+                        TemplateToken::Frame(id) => id,
+                        TemplateToken::Property(name) => {
+                            match entry.info.as_ref().and_then(|info| info.property(name)) {
+                                Some(value) => contents.push(value),
+                                None => {
+                                    error!("{} has no {} property", entry.filename, name);
+                                    self.trigger_logs = true;
+                                    continue 'entries;
+                                }
+                            }
+                            continue;
+                        }
+                    };
+
+                    if let Some(frame) = entry.tag.get(id) {
+                        match frame.content() {
+                            Content::Text(text) if id == "TRCK" => {
+                                // Add leading zero to track number, dropping the total
+                                // from the number/total format
+                                let track = text.split('/').next().unwrap_or_default();
+                                if track.len() < 2 {
+                                    contents.push(format!("0{}", track))
+                                } else {
+                                    contents.push(track.to_owned())
+                                }
+                            }
+                            Content::Text(text) => contents.push(text.clone()),
+                            _ => {
+                                error!("Content type not supported");
+                                self.trigger_logs = true;
+                                continue 'entries;
+                            }
+                        }
+                    } else {
+                        error!("{} does not contain {} frame", entry.filename, id);
+                        self.trigger_logs = true;
+                        continue 'entries;
+                    }
+                }
+
+                let mut new_name = self.template_string.clone();
+                for (i, mat) in mats.iter().enumerate() {
+                    new_name = new_name.replace(mat.as_str(), &contents[i]);
+                }
+                entry.filename = new_name;
+            }
+        }
+
+        self.update_details();
+    }
+
+    // Check the selected files and any files that could not be added in the background
+    fn verify_files(&mut self) {
+        if self.job.is_some() {
+            warn!("A background job is still running");
+            self.trigger_logs = true;
+            return;
+        }
+
+        let mut paths: Vec<PathBuf> = self
+            .target_indices()
+            .into_iter()
+            .map(|i| self.files[i].path.clone())
+            .collect();
+        paths.append(&mut self.failed_files);
+        if paths.is_empty() {
+            return;
+        }
+
+        let progress = Arc::new(Progress::default());
+        spawn_verify(paths, progress.clone(), self.job_tx.clone());
+        self.popup_stack.push(Box::new(ProgressPopup::new(
+            "Verifying Files",
+            progress.clone(),
+        )));
+        self.job = Some(progress);
+    }
+
+    // Log the problems found by a verify job and list them in a popup. Returns true
+    // if any were found.
+    pub fn finish_verify(&mut self, results: Vec<(PathBuf, Vec<String>)>) -> bool {
+        let progress = match self.job.take() {
+            Some(progress) => progress,
+            None => return false,
+        };
+        if progress.is_cancelled() {
+            info!("Verify cancelled");
+            return false;
+        }
+        self.close_progress_popup(&progress);
+
+        let mut rows = vec![];
+        let checked = results.len();
+        for (path, problems) in results {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            for problem in problems {
+                warn!("{} - {}", name, problem);
+                rows.push(vec![name.to_string(), problem]);
+            }
+        }
+        if rows.is_empty() {
+            info!("Verified {} files, no problems found", checked);
+            return false;
+        }
+
+        let header = vec!["File".to_owned(), "Problem".to_owned()];
+        let popup = TablePopup::new("Verify", TableKind::Info, header, rows);
+        self.popup_stack.push(Box::new(popup));
+        true
+    }
+
+    // Remove the progress popup of a finished job, popups opened on top of it while
+    // the job ran are left open
+    fn close_progress_popup(&mut self, progress: &Arc<Progress>) {
+        self.popup_stack
+            .retain(|popup| !popup.progress().is_some_and(|p| Arc::ptr_eq(p, progress)));
+    }
+
+    // Measure the loudness of the selected mp3 files in the background, they are
+    // treated as one album
+    fn analyse_replaygain(&mut self) {
+        if self.job.is_some() {
+            warn!("A background job is still running");
+            self.trigger_logs = true;
+            return;
+        }
+
+        let mut paths = vec![];
+        for i in self.target_indices() {
+            let entry = &self.files[i];
+            if entry.info.is_some() {
+                paths.push(entry.path.clone());
+            } else if !entry.loading {
+                warn!("{} - only mp3 files can be analysed", entry.filename);
+                self.trigger_logs = true;
+            }
+        }
+        if paths.is_empty() {
+            return;
+        }
+
+        let progress = Arc::new(Progress::default());
+        spawn_replaygain(paths, progress.clone(), self.job_tx.clone());
+        self.popup_stack.push(Box::new(ProgressPopup::new(
+            "Analysing ReplayGain",
+            progress.clone(),
+        )));
+        self.job = Some(progress);
+    }
+
+    // Add the track and album ReplayGain frames measured by a replaygain job. Returns
+    // true if any file could not be analysed.
+    pub fn finish_replaygain(&mut self, results: Vec<(PathBuf, Option<Loudness>)>) -> bool {
+        let progress = match self.job.take() {
+            Some(progress) => progress,
+            None => return false,
+        };
+        if progress.is_cancelled() {
+            info!("ReplayGain analysis cancelled");
+            return false;
+        }
+        self.close_progress_popup(&progress);
+
+        let measured: Vec<&Loudness> = results.iter().filter_map(|(_, l)| l.as_ref()).collect();
+        let failed = results.len() - measured.len();
+        let (album_gain, album_peak) = Loudness::album(&measured);
+
+        let mut tagged = 0;
+        for (path, loudness) in &results {
+            let loudness = match loudness {
+                Some(loudness) => loudness,
+                None => continue,
+            };
+            // The file may have been removed while it was analysed, files hidden by the
+            // filter in the meantime are still tagged
+            let entry = match self.files.iter_mut().find(|e| &e.path == path) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let gain = match loudness.gain() {
+                Some(gain) => gain,
+                None => {
+                    warn!("{} - silent, no ReplayGain added", entry.filename);
+                    continue;
+                }
+            };
+
+            let mut frames = vec![
+                ("REPLAYGAIN_TRACK_GAIN", format_gain(gain)),
+                ("REPLAYGAIN_TRACK_PEAK", format_peak(loudness.peak)),
+            ];
+            if let Some(album_gain) = album_gain {
+                frames.push(("REPLAYGAIN_ALBUM_GAIN", format_gain(album_gain)));
+                frames.push(("REPLAYGAIN_ALBUM_PEAK", format_peak(album_peak)));
+            }
+            for (description, value) in frames {
+                entry.tag.add_frame(ExtendedText {
+                    description: description.to_owned(),
+                    value,
+                });
+            }
+            info!("{} - track gain {}", entry.filename, format_gain(gain));
+            tagged += 1;
+        }
+
+        if let Some(album_gain) = album_gain {
+            info!(
+                "Added ReplayGain to {} files, album gain {}",
+                tagged,
+                format_gain(album_gain)
+            );
+        }
+        self.update_details();
+        failed > 0
+    }
+
+    fn spawn_duplicates_popup(&mut self) {
+        let options = DuplicateMode::ALL
+            .iter()
+            .map(|m| m.name().to_owned())
+            .collect();
+        let mode = DuplicateMode::ALL
+            .iter()
+            .position(|m| *m == self.duplicate_mode)
+            .unwrap_or(0);
+        let popup = FormPopup::new(
+            "Find Duplicates",
+            FormKind::Duplicates,
+            vec![FormField::choice("Match by", options, mode)],
+        );
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Find groups of duplicates among the files, the audio of every file is hashed in
+    // the background first if the mode compares audio
+    fn find_duplicates(&mut self, mode: DuplicateMode) {
+        self.duplicate_mode = mode;
+        if !mode.uses_audio() {
+            self.show_duplicates(HashMap::new());
+            return;
+        }
+        if self.job.is_some() {
+            warn!("A background job is still running");
+            self.trigger_logs = true;
+            return;
+        }
+
+        let paths: Vec<PathBuf> = self
+            .visible_files()
+            .filter(|e| !e.loading)
+            .map(|e| e.path.clone())
+            .collect();
+        if paths.is_empty() {
+            return;
+        }
+
+        let progress = Arc::new(Progress::default());
+        spawn_hash_audio(paths, progress.clone(), self.job_tx.clone());
+        self.popup_stack.push(Box::new(ProgressPopup::new(
+            "Hashing Audio",
+            progress.clone(),
+        )));
+        self.job = Some(progress);
+    }
+
+    // Show the duplicates found with the audio hashes of a hashing job. Returns true if
+    // any file could not be hashed.
+    pub fn finish_hash_audio(&mut self, hashes: Vec<(PathBuf, Option<u64>)>) -> bool {
+        let progress = match self.job.take() {
+            Some(progress) => progress,
+            None => return false,
+        };
+        if progress.is_cancelled() {
+            info!("Duplicate search cancelled");
+            return false;
+        }
+        self.close_progress_popup(&progress);
+
+        let failed = hashes.iter().any(|(_, hash)| hash.is_none());
+        let hashes = hashes
+            .into_iter()
+            .filter_map(|(path, hash)| Some((path, hash?)))
+            .collect();
+        self.show_duplicates(hashes);
+        failed
+    }
+
+    fn show_duplicates(&mut self, hashes: HashMap<PathBuf, u64>) {
+        let mode = self.duplicate_mode;
+        let entries: Vec<&Entry> = match mode.uses_audio() {
+            // The files hashed by the job, even if the filter has hidden them since
+            true => self
+                .files
+                .iter()
+                .filter(|e| hashes.contains_key(&e.path))
+                .collect(),
+            false => self.visible_files().filter(|e| !e.loading).collect(),
+        };
+        let compared = entries.len();
+        self.duplicates = group(entries.into_iter().map(|e| (mode.key(e, &hashes), e)));
+
+        if self.duplicates.is_empty() {
+            info!("No duplicates found among {} files", compared);
+            return;
+        }
+        let copies: usize = self.duplicates.iter().map(|g| g.len() - 1).sum();
+        info!(
+            "Found {} groups of duplicates with {} extra copies",
+            self.duplicates.len(),
+            copies
+        );
+        self.push_duplicates_popup();
+    }
+
+    fn push_duplicates_popup(&mut self) {
+        let name = |path: &PathBuf| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        };
+        let rows = self
+            .duplicates
+            .iter()
+            .map(|group| {
+                let copies: Vec<String> = group[1..].iter().map(name).collect();
+                vec![name(&group[0]), copies.join(", ")]
+            })
+            .collect();
+        let header = vec!["Keep".to_owned(), "Copies".to_owned()];
+        let popup = TablePopup::new("Duplicates", TableKind::Duplicates, header, rows);
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Remove the extra copies of a group of duplicates from the files list, the
+    // popup is shown again while there are groups left
+    fn remove_duplicates(&mut self, row: usize) {
+        if row >= self.duplicates.len() {
+            return;
+        }
+        let group = self.duplicates.remove(row);
+        let copies = &group[1..];
+
+        let highlighted = self.highlighted_path();
+        let before = self.files.len();
+        self.files.retain(|e| !copies.contains(&e.path));
+        info!(
+            "Removed {} copies of {}",
+            before - self.files.len(),
+            group[0].file_name().unwrap_or_default().to_string_lossy()
+        );
+        self.update_visible(highlighted);
+
+        if !self.duplicates.is_empty() {
+            self.push_duplicates_popup();
+        }
+    }
+
+    // Check the selected files as one album and list the problems found in a popup,
+    // problems are only logged when `log` is set so fixing one doesn't repeat them
+    fn lint_album(&mut self, log: bool) {
+        let targets = self.target_indices();
+        if targets.is_empty() {
+            return;
+        }
+        let entries: Vec<&Entry> = targets.iter().map(|i| &self.files[*i]).collect();
+        let findings = lint(&entries);
+        if findings.is_empty() {
+            info!("No album problems found in {} files", entries.len());
+            self.lint_fixes.clear();
+            return;
+        }
+
+        let mut rows = vec![];
+        self.lint_fixes = vec![];
+        for finding in findings {
+            if log {
+                warn!("{}", finding.message);
+            }
+            let fix = finding.fix.as_ref().map_or("", |f| f.description.as_str());
+            rows.push(vec![finding.message.clone(), fix.to_owned()]);
+            self.lint_fixes.push(finding.fix);
+        }
+        if log {
+            self.trigger_logs = true;
+        }
+
+        let header = vec!["Problem".to_owned(), "Fix".to_owned()];
+        let popup = TablePopup::new("Album Lint", TableKind::Lint, header, rows);
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    // Apply the fix of a problem found by the album linter and check the album again
+    fn apply_lint_fix(&mut self, row: usize) {
+        match self.lint_fixes.get_mut(row).and_then(Option::take) {
+            Some(fix) => {
+                info!("{}", fix.description);
+                self.update_entries(fix.changes);
+            }
+            None => {
+                warn!("This problem has no automatic fix");
+                self.trigger_logs = true;
+            }
+        }
+        self.lint_album(false);
+    }
+
+    // Show the duration, bitrate and encoder details of the highlighted file
+    fn spawn_stream_info_popup(&mut self) {
+        let entry = match self.highlighted().map(|i| &self.files[i]) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let info = match &entry.info {
+            Some(info) => info,
+            None => {
+                warn!(
+                    "{} - no audio properties, only mp3 files are read",
+                    entry.filename
+                );
+                self.trigger_logs = true;
+                return;
+            }
+        };
+
+        let rows = info
+            .rows()
+            .into_iter()
+            .map(|(name, value)| vec![name, value])
+            .collect();
+        let header = vec!["Property".to_owned(), "Value".to_owned()];
+        let popup = TablePopup::new(&entry.filename, TableKind::Info, header, rows);
+        self.popup_stack.push(Box::new(popup));
+    }
+
+    pub fn popup_widget(&self) -> Option<&dyn Popup> {
+        self.popup_stack.last().map(|popup| popup.as_ref())
+    }
+
+    pub fn spawn_help_popup(&mut self, help_popup: HelpPopup) {
+        self.popup_stack.push(Box::new(help_popup));
+    }
+
+    pub fn update_help_text(&mut self, config: &Config) {
+        let quit = config.get_key(&Action::Quit).unwrap();
+        let remove_all = config.get_key(&Action::RemoveFiles).unwrap();
+        let remove = config.get_key(&Action::Remove).unwrap();
+        let select_all = config.get_key(&Action::SelectAll).unwrap();
+        let select = config.get_key(&Action::SelectCurrent).unwrap();
+        let write = config.get_key(&Action::WriteTags).unwrap();
+        let template = config.get_key(&Action::TemplatePopup).unwrap();
+        let update = config.get_key(&Action::UpdateNames).unwrap();
+        let tags_from_name = config.get_key(&Action::TagsFromName).unwrap();
+        let number_tracks = config.get_key(&Action::NumberTracks).unwrap();
+        let find_replace = config.get_key(&Action::FindReplace).unwrap();
+        let title_case = config.get_key(&Action::TitleCase).unwrap();
+        let sentence_case = config.get_key(&Action::SentenceCase).unwrap();
+        let upper_case = config.get_key(&Action::UpperCase).unwrap();
+        let lower_case = config.get_key(&Action::LowerCase).unwrap();
+        let trim = config.get_key(&Action::TrimWhitespace).unwrap();
+        let collapse = config.get_key(&Action::CollapseSpaces).unwrap();
+        let normalise = config.get_key(&Action::NormaliseUnicode).unwrap();
+        let copy_frame = config.get_key(&Action::CopyFrame).unwrap();
+        let copy_tag = config.get_key(&Action::CopyTag).unwrap();
+        let paste_merge = config.get_key(&Action::PasteMerge).unwrap();
+        let paste_overwrite = config.get_key(&Action::PasteOverwrite).unwrap();
+        let table_view = config.get_key(&Action::ToggleTableView).unwrap();
+        let column_prev = config.get_key(&Action::ColumnPrev).unwrap();
+        let column_next = config.get_key(&Action::ColumnNext).unwrap();
+        let sort_column = config.get_key(&Action::SortColumn).unwrap();
+        let sort_files = config.get_key(&Action::SortFiles).unwrap();
+        let search = config.get_key(&Action::Search).unwrap();
+        let filter = config.get_key(&Action::FilterFiles).unwrap();
+        let search_next = config.get_key(&Action::SearchNext).unwrap();
+        let search_prev = config.get_key(&Action::SearchPrev).unwrap();
+        let stream_info = config.get_key(&Action::StreamInfo).unwrap();
+        let verify = config.get_key(&Action::Verify).unwrap();
+        let replaygain = config.get_key(&Action::ReplayGain).unwrap();
+        let find_duplicates = config.get_key(&Action::FindDuplicates).unwrap();
+        let lint_album = config.get_key(&Action::LintAlbum).unwrap();
+
+        self.help_text = vec![
+            format!("`{}` - Quit", util::display_keycode(quit)),
+            format!("`{}` - Remove all files", util::display_keycode(remove_all)),
+            format!(
                 "`{}` - Remove selected files/frame",
                 util::display_keycode(remove)
             ),
@@ -791,6 +2490,256 @@ impl MainState {
                 "`{}` - Update filenames using current template",
                 util::display_keycode(update)
             ),
+            format!(
+                "`{}` - Fill frames from filenames",
+                util::display_keycode(tags_from_name)
+            ),
+            format!(
+                "`{}` - Number selected files in list order",
+                util::display_keycode(number_tracks)
+            ),
+            format!(
+                "`{}` - Find and replace in selected files",
+                util::display_keycode(find_replace)
+            ),
+            format!(
+                "`{}`/`{}`/`{}`/`{}` - Title/Sentence/Upper/Lower case frames",
+                util::display_keycode(title_case),
+                util::display_keycode(sentence_case),
+                util::display_keycode(upper_case),
+                util::display_keycode(lower_case)
+            ),
+            format!(
+                "`{}`/`{}`/`{}` - Trim/Collapse spaces/Normalise unicode in frames",
+                util::display_keycode(trim),
+                util::display_keycode(collapse),
+                util::display_keycode(normalise)
+            ),
+            format!(
+                "`{}` - Copy highlighted frame",
+                util::display_keycode(copy_frame)
+            ),
+            format!(
+                "`{}` - Copy frames from highlighted file",
+                util::display_keycode(copy_tag)
+            ),
+            format!(
+                "`{}`/`{}` - Paste frames into selected files (merge/overwrite)",
+                util::display_keycode(paste_merge),
+                util::display_keycode(paste_overwrite)
+            ),
+            format!(
+                "`{}` - Toggle table view",
+                util::display_keycode(table_view)
+            ),
+            format!(
+                "`{}`/`{}` - Highlight previous/next table column",
+                util::display_keycode(column_prev),
+                util::display_keycode(column_next)
+            ),
+            format!(
+                "`{}` - Sort files by highlighted table column",
+                util::display_keycode(sort_column)
+            ),
+            format!(
+                "`{}` - Sort files by filename, path, frame or modification time",
+                util::display_keycode(sort_files)
+            ),
+            format!(
+                "`{}` - Search files, e.g. `artist:foo album:\"bar baz\"`",
+                util::display_keycode(search)
+            ),
+            format!(
+                "`{}` - Filter files, actions only apply to matching files",
+                util::display_keycode(filter)
+            ),
+            format!(
+                "`{}`/`{}` - Highlight next/previous search match",
+                util::display_keycode(search_next),
+                util::display_keycode(search_prev)
+            ),
+            format!(
+                "`{}` - Show audio properties of highlighted file",
+                util::display_keycode(stream_info)
+            ),
+            format!(
+                "`{}` - Check selected files for damaged tags and audio",
+                util::display_keycode(verify)
+            ),
+            format!(
+                "`{}` - Add ReplayGain frames to selected mp3 files",
+                util::display_keycode(replaygain)
+            ),
+            format!(
+                "`{}` - Find duplicate files",
+                util::display_keycode(find_duplicates)
+            ),
+            format!(
+                "`{}` - Check selected files for album inconsistencies",
+                util::display_keycode(lint_album)
+            ),
         ];
     }
 }
+
+// Check if two frames would replace each other in a tag, TXXX frames are only the
+// same frame if their descriptions match
+fn same_frame(a: &Frame, b: &Frame) -> bool {
+    a.id() == b.id()
+        && a.content().extended_text().map(|t| &t.description)
+            == b.content().extended_text().map(|t| &t.description)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+
+    // A state holding files named after `tags`, with the first file highlighted
+    fn state(tags: Vec<Tag>) -> MainState {
+        let mut state = MainState::new("{artist} - {title}".to_owned(), unbounded_channel().0);
+        let files = tags
+            .into_iter()
+            .enumerate()
+            .map(|(i, tag)| Entry::new(PathBuf::from(format!("/music/{}.mp3", i)), tag))
+            .collect();
+        state.add_files(files);
+        state.files_state.select(Some(0));
+        state
+    }
+
+    fn tag(frames: &[(&str, &str)]) -> Tag {
+        let mut tag = Tag::new();
+        for (id, value) in frames {
+            tag.set_text(*id, *value);
+        }
+        tag
+    }
+
+    // Text of the pending frames of each file
+    fn pending(state: &MainState) -> Vec<Vec<String>> {
+        state
+            .pending_frames
+            .iter()
+            .map(|(_, frames)| frames.iter().map(|f| f.content().to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn track_numbers() {
+        let mut state = state(vec![
+            tag(&[("TIT2", "a")]),
+            tag(&[("TIT2", "b")]),
+            tag(&[("TIT2", "c")]),
+        ]);
+        state.select_all_entries();
+
+        state.preview_track_numbers(" 2 ", true, " 1 ");
+        assert_eq!(pending(&state), [["2/4", "1"], ["3/4", "1"], ["4/4", "1"]]);
+
+        state.preview_track_numbers("1", false, "");
+        assert_eq!(pending(&state), [["1"], ["2"], ["3"]]);
+        assert_eq!(state.popup_stack.len(), 2);
+
+        state.preview_track_numbers("one", false, "");
+        assert_eq!(state.popup_stack.len(), 2);
+    }
+
+    #[test]
+    fn edits_only_advance_from_one_file() {
+        let mut state = state(vec![tag(&[]), tag(&[]), tag(&[])]);
+        state.update_files(Frame::text("TALB", "album"));
+        assert_eq!(state.files_state.selected(), Some(1));
+
+        state.select_all_entries();
+        state.update_files(Frame::text("TCON", "genre"));
+        assert_eq!(state.files_state.selected(), Some(1));
+        assert!(state.files.iter().all(|e| e.tag.genre() == Some("genre")));
+        assert_eq!(state.files[0].tag.album(), Some("album"));
+        assert_eq!(state.files[1].tag.album(), None);
+    }
+
+    #[test]
+    fn copy_and_paste() {
+        let mut state = state(vec![
+            tag(&[("TIT2", "a"), ("TALB", "album"), ("TPE1", "artist")]),
+            tag(&[("TIT2", "b"), ("TCON", "genre")]),
+        ]);
+        state.copy_tag("album, TPE1".to_owned());
+        assert_eq!(state.clipboard.len(), 2);
+
+        state.files_state.select(Some(1));
+        state.paste_clipboard(false);
+        assert_eq!(pending(&state), [["album", "artist"]]);
+        assert_eq!(state.pending_removals[0].1, Vec::<String>::new());
+
+        state.paste_clipboard(true);
+        assert_eq!(state.pending_removals[0].1, ["TIT2", "TCON"]);
+    }
+
+    #[test]
+    fn paste_skips_loading_files() {
+        let mut state = state(vec![tag(&[("TIT2", "a")])]);
+        state.add_files(vec![Entry::loading(PathBuf::from("/music/loading.mp3"))]);
+        state.copy_tag("".to_owned());
+        state.select_all_entries();
+
+        state.paste_clipboard(false);
+        assert_eq!(state.pending_frames.len(), 1);
+        assert_eq!(state.pending_frames[0].0, PathBuf::from("/music/0.mp3"));
+    }
+
+    #[test]
+    fn details_order_and_hidden_frames() {
+        let mut state = state(vec![tag(&[
+            ("TIT2", "title"),
+            ("TCON", "genre"),
+            ("TALB", "album"),
+            ("TPE1", "artist"),
+            ("TYER", "2000"),
+        ])]);
+        state.details_order = vec!["TPE1".to_owned(), "TIT2".to_owned()];
+        state.hidden_frames = vec!["TCON".to_owned()];
+        state.update_details();
+
+        let ids: Vec<&str> = state
+            .details
+            .iter()
+            .map(|item| item.frame().map_or("", |f| f.id()))
+            .collect();
+        // Unlisted frames are sorted by name, album before year
+        assert_eq!(ids, ["", "TPE1", "TIT2", "TALB", "TYER"]);
+    }
+
+    #[test]
+    fn loading_files_are_not_changed() {
+        let mut state = state(vec![tag(&[("TIT2", "a"), ("TALB", "album")])]);
+        let loading = PathBuf::from("/music/loading.mp3");
+        state.add_files(vec![Entry::loading(loading.clone())]);
+        state.select_all_entries();
+
+        state.add_frame("TCON");
+        let album = state
+            .details
+            .iter()
+            .position(|item| item.frame().is_some_and(|f| f.id() == "TALB"))
+            .unwrap();
+        state.details_state.select(Some(album));
+        state.remove_frames();
+        assert_eq!(state.files[0].tag.album(), None);
+        assert!(state.files[0].tag.get("TCON").is_some());
+        assert_eq!(state.files[1].tag.frames().count(), 0);
+
+        state.files[0].selected = false;
+        state.files_state.select(Some(1));
+        state.update_filename("new.mp3".to_owned());
+        assert_eq!(state.files[1].filename, "loading.mp3");
+
+        state.finish_loading(loading, Some(tag(&[("TALB", "other")])), None);
+        assert!(!state.files[1].loading);
+        assert_eq!(state.files[1].tag.album(), Some("other"));
+        state.update_filename("new.mp3".to_owned());
+        assert_eq!(state.files[1].filename, "new.mp3");
+    }
+}
@@ -1,7 +1,28 @@
+pub mod album_lint;
+pub mod backend;
+pub mod columns;
+pub mod duplicates;
+pub mod file_query;
 pub mod files_state;
+pub mod find_replace;
+pub mod flac;
 pub mod frame_data;
 pub mod frames_state;
+pub mod fuzzy;
+pub mod jobs;
 pub mod main_state;
+pub mod mpeg;
+pub mod ogg;
+pub mod replaygain;
+pub mod sorting;
+pub mod tag_pattern;
+pub mod tag_preview;
+#[cfg(test)]
+pub mod test_dir;
+pub mod transforms;
+pub mod validation;
+pub mod verify;
+pub mod vorbis_comment;
 use main_state::Entry;
 
 use crate::popups::{PopupData, PopupHelpType};
@@ -0,0 +1,596 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+// How far into the audio the first frame is searched for
+const SYNC_SEARCH: usize = 64 * 1024;
+
+// Bitrates in kbps by bitrate index, for MPEG-1 and for MPEG-2 and 2.5
+const BITRATES_V1: [[u32; 15]; 3] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+];
+const BITRATES_V2: [[u32; 15]; 3] = [
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+// Names of the stream properties that can be used as template tokens
+pub const PROPERTIES: [&str; 6] = [
+    "bitrate",
+    "bitrate_mode",
+    "sample_rate",
+    "channels",
+    "duration",
+    "encoder",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpegVersion {
+    V1,
+    V2,
+    V25,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+    Stereo,
+    JointStereo,
+    DualChannel,
+    Mono,
+}
+
+impl ChannelMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChannelMode::Stereo => "Stereo",
+            ChannelMode::JointStereo => "Joint Stereo",
+            ChannelMode::DualChannel => "Dual Channel",
+            ChannelMode::Mono => "Mono",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitrateMode {
+    Constant,
+    Average,
+    Variable,
+}
+
+impl BitrateMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BitrateMode::Constant => "CBR",
+            BitrateMode::Average => "ABR",
+            BitrateMode::Variable => "VBR",
+        }
+    }
+}
+
+// The four byte header at the start of every MPEG audio frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub version: MpegVersion,
+    pub layer: u8,
+    pub protected: bool,
+    // Bitrate in kbps, free format frames are not supported
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    pub padding: bool,
+    pub channel_mode: ChannelMode,
+}
+
+impl FrameHeader {
+    // `None` if the bytes are not a valid frame header
+    pub fn parse(bytes: [u8; 4]) -> Option<Self> {
+        let header = u32::from_be_bytes(bytes);
+        if header >> 21 != 0x7ff {
+            return None;
+        }
+
+        let version = match (header >> 19) & 0b11 {
+            0b00 => MpegVersion::V25,
+            0b10 => MpegVersion::V2,
+            0b11 => MpegVersion::V1,
+            _ => return None,
+        };
+        let layer = match (header >> 17) & 0b11 {
+            0b01 => 3,
+            0b10 => 2,
+            0b11 => 1,
+            _ => return None,
+        };
+        let bitrate_index = ((header >> 12) & 0b1111) as usize;
+        if bitrate_index == 0 || bitrate_index == 15 {
+            return None;
+        }
+        let bitrate = match version {
+            MpegVersion::V1 => BITRATES_V1[layer as usize - 1][bitrate_index],
+            _ => BITRATES_V2[layer as usize - 1][bitrate_index],
+        };
+        let sample_rate = match ((header >> 10) & 0b11, version) {
+            (0b11, _) => return None,
+            (i, MpegVersion::V1) => [44100, 48000, 32000][i as usize],
+            (i, MpegVersion::V2) => [22050, 24000, 16000][i as usize],
+            (i, MpegVersion::V25) => [11025, 12000, 8000][i as usize],
+        };
+        // Reserved emphasis
+        if header & 0b11 == 0b10 {
+            return None;
+        }
+
+        Some(Self {
+            version,
+            layer,
+            protected: (header >> 16) & 1 == 0,
+            bitrate,
+            sample_rate,
+            padding: (header >> 9) & 1 == 1,
+            channel_mode: match (header >> 6) & 0b11 {
+                0b00 => ChannelMode::Stereo,
+                0b01 => ChannelMode::JointStereo,
+                0b10 => ChannelMode::DualChannel,
+                _ => ChannelMode::Mono,
+            },
+        })
+    }
+
+    pub fn samples(&self) -> u32 {
+        match (self.layer, self.version) {
+            (1, _) => 384,
+            (3, MpegVersion::V2 | MpegVersion::V25) => 576,
+            _ => 1152,
+        }
+    }
+
+    // Length of the frame in bytes, including the header
+    pub fn len(&self) -> usize {
+        let slot = if self.layer == 1 { 4 } else { 1 };
+        let slots = self.samples() / 8 / slot * self.bitrate * 1000 / self.sample_rate;
+        (slots + self.padding as u32) as usize * slot as usize
+    }
+
+    // Whether `other` could be the next frame of the same stream
+    pub fn matches(&self, other: &FrameHeader) -> bool {
+        self.version == other.version
+            && self.layer == other.layer
+            && self.sample_rate == other.sample_rate
+    }
+
+    pub fn version_name(&self) -> String {
+        let version = match self.version {
+            MpegVersion::V1 => "1",
+            MpegVersion::V2 => "2",
+            MpegVersion::V25 => "2.5",
+        };
+        format!("MPEG-{} Layer {}", version, "I".repeat(self.layer as usize))
+    }
+
+    // Offset of a Xing or Info header from the start of the frame, after the side
+    // information of the first frame
+    fn xing_offset(&self) -> usize {
+        let mono = self.channel_mode == ChannelMode::Mono;
+        4 + match (self.version, mono) {
+            (MpegVersion::V1, false) => 32,
+            (MpegVersion::V1, true) => 17,
+            (_, false) => 17,
+            (_, true) => 9,
+        }
+    }
+}
+
+// Details written by the LAME encoder after the Xing header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncoderInfo {
+    pub name: String,
+    // Lowpass filter frequency in Hz
+    pub lowpass: Option<u32>,
+    // Samples added before and after the audio by the encoder
+    pub delay: u16,
+    pub padding: u16,
+}
+
+// Audio properties of an mp3 file, read from the first frame and the Xing, Info or
+// VBRI header if there is one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub header: FrameHeader,
+    // Average bitrate in kbps
+    pub bitrate: u32,
+    pub bitrate_mode: BitrateMode,
+    pub duration: Duration,
+    // Number of audio frames, only known from a VBR header
+    pub frames: Option<u32>,
+    pub vbr_header: Option<&'static str>,
+    pub encoder: Option<EncoderInfo>,
+}
+
+impl StreamInfo {
+    // `Ok(None)` if no MPEG frames are found. Files without a VBR header are assumed
+    // to be constant bitrate so only the start of the file has to be read.
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let audio_start = id3v2_len(&mut file)?;
+        let mut audio_end = file_len;
+        if file_len >= audio_start + 128 {
+            let mut tag = [0; 3];
+            file.seek(SeekFrom::Start(file_len - 128))?;
+            file.read_exact(&mut tag)?;
+            if &tag == b"TAG" {
+                audio_end -= 128;
+            }
+        }
+
+        file.seek(SeekFrom::Start(audio_start))?;
+        let mut buf = vec![];
+        (&mut file)
+            .take(SYNC_SEARCH as u64 + 4096)
+            .read_to_end(&mut buf)?;
+        let (offset, header) = match find_frame(&buf) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let frame = &buf[offset..buf.len().min(offset + header.len())];
+        let audio_len = audio_end.saturating_sub(audio_start + offset as u64);
+
+        let mut info = Self {
+            header,
+            bitrate: header.bitrate,
+            bitrate_mode: BitrateMode::Constant,
+            duration: Duration::ZERO,
+            frames: None,
+            vbr_header: None,
+            encoder: None,
+        };
+
+        let mut bytes = None;
+        if let Some(xing) = read_xing(frame, header.xing_offset()) {
+            info.vbr_header = Some(xing.name);
+            info.frames = xing.frames;
+            bytes = xing.bytes;
+            info.bitrate_mode = match xing.name {
+                "Info" => BitrateMode::Constant,
+                _ => BitrateMode::Variable,
+            };
+            if let Some((encoder, method)) = xing.encoder {
+                info.bitrate_mode = match method {
+                    1 | 8 => BitrateMode::Constant,
+                    2 | 9 => BitrateMode::Average,
+                    3..=7 => BitrateMode::Variable,
+                    _ => info.bitrate_mode,
+                };
+                info.encoder = Some(encoder);
+            }
+        } else if let Some((vbri_bytes, frames)) = read_vbri(frame) {
+            info.vbr_header = Some("VBRI");
+            info.bitrate_mode = BitrateMode::Variable;
+            info.frames = Some(frames);
+            bytes = Some(vbri_bytes);
+        }
+
+        match info.frames {
+            Some(frames) if frames > 0 => {
+                let samples = frames as u64 * header.samples() as u64;
+                info.duration =
+                    Duration::from_micros(samples * 1_000_000 / header.sample_rate as u64);
+                let bytes = bytes.map(|b| b as u64).unwrap_or(audio_len);
+                let millis = info.duration.as_millis().max(1) as u64;
+                info.bitrate = (bytes * 8 / millis) as u32;
+            }
+            _ => {
+                let micros = audio_len * 8 * 1000 / header.bitrate as u64;
+                info.duration = Duration::from_micros(micros);
+            }
+        }
+
+        Ok(Some(info))
+    }
+
+    // Rows shown in the stream info popup
+    pub fn rows(&self) -> Vec<(String, String)> {
+        let mut rows = vec![
+            ("Format".to_owned(), self.header.version_name()),
+            ("Duration".to_owned(), format_duration(self.duration, ":")),
+            (
+                "Bitrate".to_owned(),
+                format!("{} kbps {}", self.bitrate, self.bitrate_mode.name()),
+            ),
+            (
+                "Sample rate".to_owned(),
+                format!("{} Hz", self.header.sample_rate),
+            ),
+            (
+                "Channels".to_owned(),
+                self.header.channel_mode.name().to_owned(),
+            ),
+            (
+                "VBR header".to_owned(),
+                self.vbr_header.unwrap_or("None").to_owned(),
+            ),
+        ];
+        if let Some(frames) = self.frames {
+            rows.push(("Frames".to_owned(), frames.to_string()));
+        }
+        if let Some(encoder) = &self.encoder {
+            rows.push(("Encoder".to_owned(), encoder.name.clone()));
+            if let Some(lowpass) = encoder.lowpass {
+                rows.push(("Lowpass".to_owned(), format!("{} Hz", lowpass)));
+            }
+            rows.push((
+                "Encoder delay".to_owned(),
+                format!("{} samples", encoder.delay),
+            ));
+            rows.push((
+                "Encoder padding".to_owned(),
+                format!("{} samples", encoder.padding),
+            ));
+        }
+        rows
+    }
+
+    // Value of one of `PROPERTIES`, for use in filenames
+    pub fn property(&self, name: &str) -> Option<String> {
+        match name {
+            "bitrate" => Some(self.bitrate.to_string()),
+            "bitrate_mode" => Some(self.bitrate_mode.name().to_owned()),
+            "sample_rate" => Some(self.header.sample_rate.to_string()),
+            "channels" => Some(self.header.channel_mode.name().to_owned()),
+            "duration" => Some(format_duration(self.duration, ".")),
+            "encoder" => self.encoder.as_ref().map(|e| e.name.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Minutes and seconds separated by `separator`
+pub fn format_duration(duration: Duration, separator: &str) -> String {
+    let seconds = duration.as_secs();
+    format!("{}{}{:02}", seconds / 60, separator, seconds % 60)
+}
+
+// Length of the ID3v2 tag at the start of the file, zero if there is none
+pub fn id3v2_len(file: &mut File) -> io::Result<u64> {
+    let mut header = [0; 10];
+    file.seek(SeekFrom::Start(0))?;
+    match file.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(0),
+        Err(e) => return Err(e),
+    }
+    if &header[..3] != b"ID3" {
+        return Ok(0);
+    }
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |size, b| (size << 7) | (*b & 0x7f) as u64);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    Ok(10 + size + footer)
+}
+
+// Length of the ID3v1 and APEv2 tags at the end of the audio data in `data`
+pub fn trailing_tags_len(data: &[u8]) -> usize {
+    let mut len = 0;
+    if data.len() >= 128 && &data[data.len() - 128..data.len() - 125] == b"TAG" {
+        len += 128;
+    }
+    if let Some(ape) = ape_tag_len(&data[..data.len() - len]) {
+        len += ape;
+    }
+    len
+}
+
+// Length of an APEv2 tag at the end of `data`, including its header
+fn ape_tag_len(data: &[u8]) -> Option<usize> {
+    let footer = data.get(data.len().checked_sub(32)?..)?;
+    if &footer[..8] != b"APETAGEX" {
+        return None;
+    }
+    let size = u32::from_le_bytes(footer[12..16].try_into().unwrap()) as usize;
+    let has_header = footer[23] & 0x80 != 0;
+    let len = size + if has_header { 32 } else { 0 };
+    (len <= data.len()).then_some(len)
+}
+
+// Find the first frame in `buf` that is followed by another frame of the same stream,
+// which rules out most false syncs in junk data. Returns its offset and header.
+pub fn find_frame(buf: &[u8]) -> Option<(usize, FrameHeader)> {
+    let end = buf.len().min(SYNC_SEARCH);
+    (0..end).find_map(|offset| {
+        let header = header_at(buf, offset)?;
+        let next = offset + header.len();
+        // A frame at the end of the buffer can't be checked against the next one
+        if next + 4 > buf.len() {
+            return Some((offset, header));
+        }
+        match header_at(buf, next) {
+            Some(next_header) if header.matches(&next_header) => Some((offset, header)),
+            _ => None,
+        }
+    })
+}
+
+pub fn header_at(buf: &[u8], offset: usize) -> Option<FrameHeader> {
+    let bytes = buf.get(offset..offset + 4)?;
+    FrameHeader::parse(bytes.try_into().unwrap())
+}
+
+struct XingHeader {
+    name: &'static str,
+    frames: Option<u32>,
+    bytes: Option<u32>,
+    // LAME tag and its VBR method
+    encoder: Option<(EncoderInfo, u8)>,
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_xing(frame: &[u8], offset: usize) -> Option<XingHeader> {
+    let name = match frame.get(offset..offset + 4)? {
+        b"Xing" => "Xing",
+        b"Info" => "Info",
+        _ => return None,
+    };
+    let flags = read_u32(frame, offset + 4)?;
+    let mut pos = offset + 8;
+    let mut xing = XingHeader {
+        name,
+        frames: None,
+        bytes: None,
+        encoder: None,
+    };
+    if flags & 0x1 != 0 {
+        xing.frames = read_u32(frame, pos);
+        pos += 4;
+    }
+    if flags & 0x2 != 0 {
+        xing.bytes = read_u32(frame, pos);
+        pos += 4;
+    }
+    // Table of contents and quality
+    if flags & 0x4 != 0 {
+        pos += 100;
+    }
+    if flags & 0x8 != 0 {
+        pos += 4;
+    }
+
+    // The LAME tag starts with a 9 byte encoder version such as `LAME3.100`
+    if let Some(lame) = frame.get(pos..pos + 24) {
+        let name = String::from_utf8_lossy(&lame[..9]);
+        let name = name.trim_end_matches(['\0', ' ']).to_owned();
+        if name.chars().all(|c| c.is_ascii_graphic()) && !name.is_empty() {
+            let method = lame[9] & 0x0f;
+            let lowpass = match lame[10] {
+                0 => None,
+                l => Some(l as u32 * 100),
+            };
+            let delay = ((lame[21] as u16) << 4) | (lame[22] as u16 >> 4);
+            let padding = ((lame[22] as u16 & 0x0f) << 8) | lame[23] as u16;
+            let encoder = EncoderInfo {
+                name,
+                lowpass,
+                delay,
+                padding,
+            };
+            xing.encoder = Some((encoder, method));
+        }
+    }
+    Some(xing)
+}
+
+// The VBRI header written by the Fraunhofer encoder, always 32 bytes after the frame
+// header. Returns the number of bytes and frames.
+fn read_vbri(frame: &[u8]) -> Option<(u32, u32)> {
+    if frame.get(36..40)? != b"VBRI" {
+        return None;
+    }
+    Some((read_u32(frame, 46)?, read_u32(frame, 50)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_dir::TestDir;
+
+    // MPEG-1 Layer III, 128 kbps, 44100 Hz, joint stereo
+    const HEADER: [u8; 4] = [0xff, 0xfb, 0x90, 0x64];
+
+    fn frame() -> Vec<u8> {
+        let mut frame = vec![0; FrameHeader::parse(HEADER).unwrap().len()];
+        frame[..4].copy_from_slice(&HEADER);
+        frame
+    }
+
+    #[test]
+    fn parse_header() {
+        let header = FrameHeader::parse(HEADER).unwrap();
+        assert_eq!(header.version, MpegVersion::V1);
+        assert_eq!(header.layer, 3);
+        assert_eq!(header.bitrate, 128);
+        assert_eq!(header.sample_rate, 44100);
+        assert_eq!(header.channel_mode, ChannelMode::JointStereo);
+        assert_eq!(header.len(), 417);
+        assert_eq!(header.version_name(), "MPEG-1 Layer III");
+
+        // Reserved version, bad bitrate and reserved sample rate
+        assert_eq!(FrameHeader::parse([0xff, 0xeb, 0x90, 0x64]), None);
+        assert_eq!(FrameHeader::parse([0xff, 0xfb, 0xf0, 0x64]), None);
+        assert_eq!(FrameHeader::parse([0xff, 0xfb, 0x9c, 0x64]), None);
+    }
+
+    #[test]
+    fn find_frame_skips_false_sync() {
+        let mut buf = vec![0, 0xff, 0xfb, 0x90, 0x64, 0];
+        buf.extend(frame());
+        buf.extend(frame());
+        let (offset, _) = find_frame(&buf).unwrap();
+        assert_eq!(offset, 6);
+    }
+
+    #[test]
+    fn trailing_tags() {
+        let mut data = frame();
+        let mut ape_footer = b"APETAGEX".to_vec();
+        ape_footer.extend_from_slice(&2000u32.to_le_bytes());
+        ape_footer.extend_from_slice(&50u32.to_le_bytes());
+        ape_footer.extend_from_slice(&[0; 16]);
+        data.extend(vec![1; 18]);
+        data.extend(&ape_footer);
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.resize(128, 0);
+        data.extend(&id3v1);
+        assert_eq!(trailing_tags_len(&data), 128 + 50);
+    }
+
+    #[test]
+    fn read_xing_header() {
+        let mut first = frame();
+        let xing = FrameHeader::parse(HEADER).unwrap().xing_offset();
+        first[xing..xing + 4].copy_from_slice(b"Xing");
+        first[xing + 4..xing + 8].copy_from_slice(&3u32.to_be_bytes());
+        first[xing + 8..xing + 12].copy_from_slice(&100u32.to_be_bytes());
+        first[xing + 12..xing + 16].copy_from_slice(&41700u32.to_be_bytes());
+        let lame = xing + 16;
+        first[lame..lame + 9].copy_from_slice(b"LAME3.100");
+        first[lame + 9] = 0x03;
+        first[lame + 10] = 200;
+        first[lame + 21..lame + 24].copy_from_slice(&[0x24, 0x03, 0xe8]);
+
+        let mut bytes = b"ID3\x04\0\0\0\0\0\x0a".to_vec();
+        bytes.extend(vec![0; 10]);
+        bytes.extend(first);
+        bytes.extend(frame());
+        let dir = TestDir::new("mpeg");
+        let info = StreamInfo::read(&dir.file("a.mp3", &bytes))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(info.vbr_header, Some("Xing"));
+        assert_eq!(info.frames, Some(100));
+        assert_eq!(info.bitrate_mode, BitrateMode::Variable);
+        assert_eq!(info.duration.as_millis(), 2612);
+        assert_eq!(info.bitrate, 127);
+        let encoder = info.encoder.unwrap();
+        assert_eq!(encoder.name, "LAME3.100");
+        assert_eq!(encoder.lowpass, Some(20000));
+        assert_eq!((encoder.delay, encoder.padding), (576, 1000));
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    fs::File,
+    hash::Hasher,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use id3::{Error, ErrorKind, Tag};
+
+use crate::state::{
+    backend::{replace_file, TagBackend},
+    vorbis_comment::{add_pictures, VorbisComments},
+};
+
+const CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+// Header type flag set on pages that start in the middle of a packet
+const CONTINUED: u8 = 0x01;
+// Header type flag set on the last page of a logical stream
+const END_OF_STREAM: u8 = 0x04;
+// Granule position of a page on which no packet ends
+const NO_GRANULE: u64 = u64::MAX;
+const CRC_TABLE: [u32; 256] = crc_table();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Codec {
+    Vorbis,
+    Opus,
+}
+
+impl Codec {
+    fn detect(id_packet: &[u8]) -> id3::Result<Self> {
+        if id_packet.starts_with(b"\x01vorbis") {
+            Ok(Codec::Vorbis)
+        } else if id_packet.starts_with(b"OpusHead") {
+            Ok(Codec::Opus)
+        } else {
+            Err(Error::new(
+                ErrorKind::UnsupportedFeature,
+                "Ogg stream is not Vorbis or Opus",
+            ))
+        }
+    }
+
+    // Start of the comment header packet
+    fn comment_magic(&self) -> &'static [u8] {
+        match self {
+            Codec::Vorbis => b"\x03vorbis",
+            Codec::Opus => b"OpusTags",
+        }
+    }
+
+    // Vorbis has a setup header after the comment header, audio starts on the page
+    // after the last header packet for both codecs
+    fn header_packets(&self) -> usize {
+        match self {
+            Codec::Vorbis => 3,
+            Codec::Opus => 2,
+        }
+    }
+}
+
+// Comment header of Ogg Vorbis and Opus files. Writing replaces the header pages and
+// renumbers the pages after them when the number of header pages changes.
+pub struct Ogg;
+
+impl TagBackend for Ogg {
+    fn read(&self, path: &Path) -> id3::Result<Tag> {
+        let headers = Headers::read(&mut BufReader::new(File::open(path)?))?;
+        let comments = headers.comments()?;
+        if comments.fields.is_empty() {
+            return Err(Error::new(ErrorKind::NoTag, "No comments found"));
+        }
+
+        let mut tag = comments.to_tag();
+        add_pictures(&mut tag, comments.pictures());
+        Ok(tag)
+    }
+
+    fn write(&self, tag: &Tag, path: &Path) -> id3::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let headers = Headers::read(&mut reader)?;
+
+        let mut comments = headers.comments()?;
+        comments.set_from_tag(tag);
+        comments.set_pictures(tag);
+        if headers.codec == Codec::Vorbis && comments.trailing.is_empty() {
+            // Framing bit
+            comments.trailing.push(1);
+        }
+
+        let mut comment_packet = headers.codec.comment_magic().to_vec();
+        comment_packet.extend_from_slice(&comments.to_bytes());
+        let mut packets = vec![comment_packet];
+        packets.extend(headers.packets[2..].iter().cloned());
+        let pages = paginate(&packets, headers.first_page.serial, 1);
+
+        let offset = (1 + pages.len()) as i64 - headers.pages as i64;
+        reader.seek(SeekFrom::Start(headers.end))?;
+        replace_file(path, |out| {
+            out.write_all(&headers.first_page.to_bytes())?;
+            for page in &pages {
+                out.write_all(&page.to_bytes())?;
+            }
+            // Anything after the renumbered pages is copied as it is
+            if offset != 0 {
+                renumber_pages(&mut reader, out, headers.first_page.serial, offset)?;
+            }
+            io::copy(&mut reader, out)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn hash_audio(&self, path: &Path, state: &mut dyn Hasher) -> id3::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Headers::read(&mut reader)?;
+        // Page headers change when the pages are renumbered, only their data is hashed
+        while let Some(page) = Page::read(&mut reader)? {
+            state.write(&page.data);
+        }
+        Ok(())
+    }
+
+    fn tag_name(&self, _tag: &Tag) -> String {
+        "Ogg vorbis comment".to_owned()
+    }
+}
+
+// The header packets at the start of the first logical stream of an Ogg file
+struct Headers {
+    codec: Codec,
+    first_page: Page,
+    packets: Vec<Vec<u8>>,
+    // Number of pages holding the header packets, including the first page
+    pages: u32,
+    // Offset of the first page after the headers
+    end: u64,
+}
+
+impl Headers {
+    fn read(reader: &mut impl Read) -> id3::Result<Self> {
+        let not_ogg = || Error::new(ErrorKind::Parsing, "Not an Ogg file");
+        let first_page = Page::read(reader)?.ok_or_else(not_ogg)?;
+        let id_packet = first_page.data.clone();
+        let codec = Codec::detect(&id_packet)?;
+
+        let mut packets = vec![id_packet];
+        let mut packet = vec![];
+        let mut pages = 1;
+        let mut end = first_page.len() as u64;
+        while packets.len() < codec.header_packets() {
+            let page = Page::read(reader)?.ok_or_else(|| {
+                Error::new(ErrorKind::Parsing, "Ogg file ends inside the headers")
+            })?;
+            if page.serial != first_page.serial {
+                return Err(Error::new(
+                    ErrorKind::UnsupportedFeature,
+                    "Multiplexed Ogg streams are not supported",
+                ));
+            }
+
+            let mut start = 0;
+            for segment in &page.segments {
+                let len = *segment as usize;
+                packet.extend_from_slice(&page.data[start..start + len]);
+                start += len;
+                if len < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                }
+            }
+            pages += 1;
+            end += page.len() as u64;
+        }
+        if !packet.is_empty() || packets.len() != codec.header_packets() {
+            return Err(Error::new(
+                ErrorKind::Parsing,
+                "Ogg headers do not end on a page boundary",
+            ));
+        }
+
+        Ok(Self {
+            codec,
+            first_page,
+            packets,
+            pages,
+            end,
+        })
+    }
+
+    fn comments(&self) -> id3::Result<VorbisComments> {
+        match self.packets[1].strip_prefix(self.codec.comment_magic()) {
+            Some(data) => VorbisComments::parse(data),
+            None => Err(Error::new(ErrorKind::Parsing, "Missing comment header")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Page {
+    header_type: u8,
+    granule: u64,
+    serial: u32,
+    sequence: u32,
+    // Lacing values, a segment shorter than 255 bytes ends a packet
+    segments: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl Page {
+    // Read the next page, `None` at the end of the file
+    fn read(reader: &mut impl Read) -> id3::Result<Option<Self>> {
+        let mut header = [0; 27];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        if &header[..4] != CAPTURE_PATTERN || header[4] != 0 {
+            return Err(Error::new(ErrorKind::Parsing, "Invalid Ogg page"));
+        }
+
+        let mut segments = vec![0; header[26] as usize];
+        reader.read_exact(&mut segments)?;
+        let mut data = vec![0; segments.iter().map(|s| *s as usize).sum()];
+        reader.read_exact(&mut data)?;
+
+        let page = Self {
+            header_type: header[5],
+            granule: u64::from_le_bytes(header[6..14].try_into().unwrap()),
+            serial: u32::from_le_bytes(header[14..18].try_into().unwrap()),
+            sequence: u32::from_le_bytes(header[18..22].try_into().unwrap()),
+            segments,
+            data,
+        };
+        let checksum = u32::from_le_bytes(header[22..26].try_into().unwrap());
+        if page.checksum() != checksum {
+            return Err(Error::new(ErrorKind::Parsing, "Ogg page checksum mismatch"));
+        }
+        Ok(Some(page))
+    }
+
+    fn len(&self) -> usize {
+        27 + self.segments.len() + self.data.len()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.bytes_without_checksum();
+        let checksum = crc(&bytes);
+        bytes[22..26].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    fn checksum(&self) -> u32 {
+        crc(&self.bytes_without_checksum())
+    }
+
+    fn bytes_without_checksum(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len());
+        bytes.extend_from_slice(CAPTURE_PATTERN);
+        bytes.push(0);
+        bytes.push(self.header_type);
+        bytes.extend_from_slice(&self.granule.to_le_bytes());
+        bytes.extend_from_slice(&self.serial.to_le_bytes());
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.push(self.segments.len() as u8);
+        bytes.extend_from_slice(&self.segments);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+}
+
+// Write the pages of the stream with `serial` to `out` with their sequence numbers
+// moved by `offset`, up to the last page of the stream. `reader` is left at the start
+// of anything else, such as a chained stream, an ID3v1 or APE tag or a page cut short
+// at the end of the file.
+fn renumber_pages(
+    reader: &mut (impl Read + Seek),
+    out: &mut impl Write,
+    serial: u32,
+    offset: i64,
+) -> id3::Result<()> {
+    loop {
+        let position = reader.stream_position()?;
+        let mut page = match Page::read(reader) {
+            Ok(Some(page)) if page.serial == serial => page,
+            _ => {
+                reader.seek(SeekFrom::Start(position))?;
+                return Ok(());
+            }
+        };
+        page.sequence = (page.sequence as i64 + offset) as u32;
+        out.write_all(&page.to_bytes())?;
+        if page.header_type & END_OF_STREAM != 0 {
+            return Ok(());
+        }
+    }
+}
+
+// Split header packets into pages numbered from `sequence`, the last packet ends
+// its page so audio can start on a fresh page
+fn paginate(packets: &[Vec<u8>], serial: u32, sequence: u32) -> Vec<Page> {
+    let new_page = |header_type, sequence| Page {
+        header_type,
+        granule: NO_GRANULE,
+        serial,
+        sequence,
+        segments: vec![],
+        data: vec![],
+    };
+
+    let mut pages = vec![];
+    let mut page = new_page(0, sequence);
+    for packet in packets {
+        let mut start = 0;
+        loop {
+            if page.segments.len() == 255 {
+                let header_type = if start > 0 { CONTINUED } else { 0 };
+                let next = new_page(header_type, page.sequence + 1);
+                pages.push(std::mem::replace(&mut page, next));
+            }
+            let len = (packet.len() - start).min(255);
+            page.segments.push(len as u8);
+            page.data.extend_from_slice(&packet[start..start + len]);
+            start += len;
+            if len < 255 {
+                // Header pages always have a granule position of zero
+                page.granule = 0;
+                break;
+            }
+        }
+    }
+    if !page.segments.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut r = (i as u32) << 24;
+        let mut j = 0;
+        while j < 8 {
+            r = if r & 0x8000_0000 != 0 {
+                (r << 1) ^ 0x04c1_1db7
+            } else {
+                r << 1
+            };
+            j += 1;
+        }
+        table[i] = r;
+        i += 1;
+    }
+    table
+}
+
+fn crc(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |crc, b| {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ b) as usize]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use id3::{
+        frame::{Picture, PictureType},
+        TagLike,
+    };
+
+    use super::*;
+    use crate::state::test_dir::TestDir;
+
+    const SERIAL: u32 = 0x1234;
+
+    // An Opus file with an OpusTags header holding binary data after the comments
+    // and two audio pages
+    fn opus_file(dir: &TestDir) -> (PathBuf, Vec<Page>) {
+        let mut tags = b"OpusTags".to_vec();
+        let comments = VorbisComments {
+            vendor: "test".to_owned(),
+            fields: vec![
+                ("TITLE".to_owned(), "Song".to_owned()),
+                ("MOOD".to_owned(), "Calm".to_owned()),
+            ],
+            trailing: vec![1, 2, 3],
+        };
+        tags.extend_from_slice(&comments.to_bytes());
+
+        let mut pages = vec![Page {
+            header_type: 0x02,
+            granule: 0,
+            serial: SERIAL,
+            sequence: 0,
+            segments: vec![19],
+            data: b"OpusHead\x01\x02\x38\x01\x80\xbb\0\0\0\0\0".to_vec(),
+        }];
+        pages.extend(paginate(&[tags], SERIAL, 1));
+        for (i, granule) in [960, 1920].into_iter().enumerate() {
+            pages.push(Page {
+                header_type: 0,
+                granule,
+                serial: SERIAL,
+                sequence: 2 + i as u32,
+                segments: vec![3],
+                data: vec![i as u8; 3],
+            });
+        }
+
+        let bytes: Vec<u8> = pages.iter().flat_map(|p| p.to_bytes()).collect();
+        (dir.file("a.opus", &bytes), pages)
+    }
+
+    fn read_pages(path: &Path) -> Vec<Page> {
+        let mut reader = BufReader::new(File::open(path).unwrap());
+        let mut pages = vec![];
+        while let Some(page) = Page::read(&mut reader).unwrap() {
+            pages.push(page);
+        }
+        pages
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc(b"123456789"), 0x89a1_897f);
+        assert_eq!(crc(b""), 0);
+    }
+
+    #[test]
+    fn page_round_trip() {
+        let page = Page {
+            header_type: CONTINUED,
+            granule: 42,
+            serial: SERIAL,
+            sequence: 7,
+            segments: vec![255, 10],
+            data: vec![9; 265],
+        };
+        let bytes = page.to_bytes();
+        let read = Page::read(&mut &bytes[..]).unwrap().unwrap();
+        assert_eq!(read.to_bytes(), bytes);
+
+        let mut corrupt = bytes.clone();
+        corrupt[40] ^= 1;
+        assert!(Page::read(&mut &corrupt[..]).is_err());
+    }
+
+    #[test]
+    fn paginate_long_packet() {
+        let packet = vec![7; 255 * 300];
+        let pages = paginate(std::slice::from_ref(&packet), SERIAL, 1);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].header_type, CONTINUED);
+        assert_eq!(pages[1].sequence, 2);
+        // A packet that is a multiple of 255 bytes ends with an empty segment
+        assert_eq!(pages[1].segments.last(), Some(&0));
+        let data: Vec<u8> = pages.iter().flat_map(|p| p.data.clone()).collect();
+        assert_eq!(data, packet);
+    }
+
+    #[test]
+    fn write_in_place() {
+        let dir = TestDir::new("ogg");
+        let (path, original) = opus_file(&dir);
+        let mut tag = Ogg.read(&path).unwrap();
+        assert_eq!(tag.title(), Some("Song"));
+        tag.set_title("Other");
+        Ogg.write(&tag, &path).unwrap();
+
+        let tag = Ogg.read(&path).unwrap();
+        assert_eq!(tag.title(), Some("Other"));
+        assert_eq!(tag.extended_texts().next().unwrap().value, "Calm");
+        let headers = Headers::read(&mut BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert!(headers.packets[1].ends_with(&[1, 2, 3]));
+
+        let pages = read_pages(&path);
+        assert_eq!(pages.len(), original.len());
+        assert_eq!(pages[2].to_bytes(), original[2].to_bytes());
+        assert_eq!(pages[3].to_bytes(), original[3].to_bytes());
+    }
+
+    #[test]
+    fn write_renumbers_pages() {
+        let dir = TestDir::new("ogg");
+        let (path, original) = opus_file(&dir);
+        let mut tag = Ogg.read(&path).unwrap();
+        let picture = Picture {
+            mime_type: "image/png".to_owned(),
+            picture_type: PictureType::CoverFront,
+            description: "".to_owned(),
+            data: vec![5; 100_000],
+        };
+        tag.add_frame(picture.clone());
+        Ogg.write(&tag, &path).unwrap();
+
+        let pages = read_pages(&path);
+        assert!(pages.len() > original.len());
+        for (i, page) in pages.iter().enumerate() {
+            assert_eq!(page.sequence, i as u32);
+        }
+        let audio = &pages[pages.len() - 2..];
+        assert_eq!(audio[0].data, original[2].data);
+        assert_eq!(audio[1].granule, 1920);
+
+        let tag = Ogg.read(&path).unwrap();
+        assert_eq!(tag.title(), Some("Song"));
+        assert!(tag.pictures().eq([&picture]));
+    }
+
+    #[test]
+    fn write_keeps_data_after_pages() {
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.resize(128, 0);
+        let truncated = Page {
+            header_type: 0,
+            granule: 2880,
+            serial: SERIAL,
+            sequence: 4,
+            segments: vec![100],
+            data: vec![3; 100],
+        }
+        .to_bytes()[..60]
+            .to_vec();
+
+        for trailing in [id3v1, truncated] {
+            let dir = TestDir::new("ogg");
+            let (path, original) = opus_file(&dir);
+            let mut file = File::options().append(true).open(&path).unwrap();
+            file.write_all(&trailing).unwrap();
+
+            let mut tag = Ogg.read(&path).unwrap();
+            tag.add_frame(Picture {
+                mime_type: "image/png".to_owned(),
+                picture_type: PictureType::CoverFront,
+                description: "".to_owned(),
+                data: vec![5; 100_000],
+            });
+            Ogg.write(&tag, &path).unwrap();
+
+            let bytes = fs::read(&path).unwrap();
+            assert!(bytes.ends_with(&trailing));
+            let mut reader = &bytes[..bytes.len() - trailing.len()];
+            let mut pages = vec![];
+            while let Some(page) = Page::read(&mut reader).unwrap() {
+                pages.push(page);
+            }
+            assert!(pages.len() > original.len());
+            assert_eq!(pages.last().unwrap().sequence as usize, pages.len() - 1);
+            assert_eq!(pages.last().unwrap().data, original[3].data);
+        }
+    }
+
+    #[test]
+    fn write_stops_renumbering_at_end_of_stream() {
+        let dir = TestDir::new("ogg");
+        let (path, mut original) = opus_file(&dir);
+        // A second stream chained after the first keeps its own page numbers
+        original[3].header_type = END_OF_STREAM;
+        let chained = Page {
+            header_type: 0x02,
+            granule: 0,
+            serial: SERIAL + 1,
+            sequence: 0,
+            segments: vec![19],
+            data: original[0].data.clone(),
+        };
+        let mut bytes: Vec<u8> = original.iter().flat_map(|p| p.to_bytes()).collect();
+        bytes.extend(chained.to_bytes());
+        fs::write(&path, bytes).unwrap();
+
+        let mut tag = Ogg.read(&path).unwrap();
+        tag.add_frame(Picture {
+            mime_type: "image/png".to_owned(),
+            picture_type: PictureType::CoverFront,
+            description: "".to_owned(),
+            data: vec![5; 100_000],
+        });
+        Ogg.write(&tag, &path).unwrap();
+
+        let pages = read_pages(&path);
+        let last = pages.len() - 1;
+        assert_eq!(pages[last - 1].sequence as usize, last - 1);
+        assert_eq!(pages[last].to_bytes(), chained.to_bytes());
+    }
+}
@@ -0,0 +1,279 @@
+use std::{f64::consts::PI, fs::File, io, path::Path};
+
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error, formats::FormatOptions,
+    io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+// ReplayGain 2.0 reference loudness in LUFS
+const REFERENCE: f64 = -18.0;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+// Loudness of one file measured as in EBU R128: the mean square of the K-weighted
+// audio in 400ms blocks overlapping by 75%, and the largest sample value
+#[derive(Debug, Clone, Default)]
+pub struct Loudness {
+    blocks: Vec<f64>,
+    pub peak: f32,
+}
+
+impl Loudness {
+    // Decode an mp3 file and measure it, `cancelled` is checked between packets and
+    // returns `Ok(None)` once true
+    pub fn measure(path: &Path, cancelled: impl Fn() -> bool) -> Result<Option<Self>, Error> {
+        let source = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(ext);
+        }
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?;
+        let mut format = probed.format;
+        let track = format
+            .default_track()
+            .ok_or(Error::Unsupported("no audio track"))?;
+        let track_id = track.id;
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut meter: Option<Meter> = None;
+        let mut samples: Option<SampleBuffer<f32>> = None;
+        loop {
+            if cancelled() {
+                return Ok(None);
+            }
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // Damaged frames are skipped like a player would
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            let spec = *decoded.spec();
+            let buffer = match &mut samples {
+                Some(buffer) if buffer.capacity() >= decoded.capacity() * spec.channels.count() => {
+                    buffer
+                }
+                _ => samples.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+            };
+            buffer.copy_interleaved_ref(decoded);
+
+            let meter = meter.get_or_insert_with(|| Meter::new(spec.rate, spec.channels.count()));
+            meter.process(buffer.samples());
+        }
+
+        Ok(Some(meter.map(Meter::finish).unwrap_or_default()))
+    }
+
+    // Integrated loudness in LUFS, `None` for silence
+    fn integrated(blocks: &[f64]) -> Option<f64> {
+        let loudness = |z: f64| -0.691 + 10.0 * z.log10();
+        let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+        let absolute: Vec<f64> = blocks
+            .iter()
+            .copied()
+            .filter(|z| loudness(*z) > ABSOLUTE_GATE)
+            .collect();
+        if absolute.is_empty() {
+            return None;
+        }
+        let threshold = loudness(mean(&absolute)) + RELATIVE_GATE;
+        let relative: Vec<f64> = absolute
+            .into_iter()
+            .filter(|z| loudness(*z) > threshold)
+            .collect();
+        Some(loudness(mean(&relative)))
+    }
+
+    // Gain in dB that brings the file to the reference loudness
+    pub fn gain(&self) -> Option<f64> {
+        Self::integrated(&self.blocks).map(|lufs| REFERENCE - lufs)
+    }
+
+    // Gain and peak of a group of files played together, the blocks of every file are
+    // gated together
+    pub fn album(tracks: &[&Loudness]) -> (Option<f64>, f32) {
+        let blocks: Vec<f64> = tracks
+            .iter()
+            .flat_map(|t| t.blocks.iter().copied())
+            .collect();
+        let peak = tracks.iter().map(|t| t.peak).fold(0.0, f32::max);
+        (Self::integrated(&blocks).map(|lufs| REFERENCE - lufs), peak)
+    }
+}
+
+// Values of the REPLAYGAIN_* TXXX frames
+pub fn format_gain(gain: f64) -> String {
+    format!("{:.2} dB", gain)
+}
+
+pub fn format_peak(peak: f32) -> String {
+    format!("{:.6}", peak)
+}
+
+// A second order IIR filter
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[1] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[2] * y;
+        y
+    }
+}
+
+// The K-weighting filter of ITU-R BS.1770, a high shelf followed by a high pass,
+// calculated for any sample rate
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = rate as f64;
+
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+// Collects the energy of K-weighted audio in 100ms steps, four steps make a block
+struct Meter {
+    filters: Vec<[Biquad; 2]>,
+    step_len: usize,
+    // Samples in the current step and the sum of their squares, over all channels
+    step_samples: usize,
+    step_energy: f64,
+    steps: Vec<f64>,
+    peak: f32,
+}
+
+impl Meter {
+    fn new(rate: u32, channels: usize) -> Self {
+        Self {
+            filters: vec![k_weighting(rate); channels.max(1)],
+            step_len: (rate as usize / 10).max(1),
+            step_samples: 0,
+            step_energy: 0.0,
+            steps: vec![],
+            peak: 0.0,
+        }
+    }
+
+    fn process(&mut self, interleaved: &[f32]) {
+        for frame in interleaved.chunks(self.filters.len()) {
+            for (sample, [shelf, high_pass]) in frame.iter().zip(self.filters.iter_mut()) {
+                self.peak = self.peak.max(sample.abs());
+                let y = high_pass.process(shelf.process(*sample as f64));
+                self.step_energy += y * y;
+            }
+            self.step_samples += 1;
+            if self.step_samples == self.step_len {
+                self.steps.push(self.step_energy / self.step_len as f64);
+                self.step_samples = 0;
+                self.step_energy = 0.0;
+            }
+        }
+    }
+
+    fn finish(self) -> Loudness {
+        let blocks = self
+            .steps
+            .windows(4)
+            .map(|steps| steps.iter().sum::<f64>() / 4.0)
+            .collect();
+        Loudness {
+            blocks,
+            peak: self.peak,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stereo sine at `amplitude`, `seconds` long
+    fn measure_sine(rate: u32, frequency: f64, amplitude: f64, seconds: f64) -> Loudness {
+        let mut meter = Meter::new(rate, 2);
+        let samples: Vec<f32> = (0..(rate as f64 * seconds) as usize)
+            .flat_map(|i| {
+                let x = amplitude * (2.0 * PI * frequency * i as f64 / rate as f64).sin();
+                [x as f32, x as f32]
+            })
+            .collect();
+        // Fed in uneven chunks as a decoder would
+        for chunk in samples.chunks(1151 * 2) {
+            meter.process(chunk);
+        }
+        meter.finish()
+    }
+
+    #[test]
+    fn reference_sine() {
+        // A 997 Hz stereo sine at -23 dBFS measures -23 LUFS in EBU R128
+        let amplitude = 10f64.powf(-23.0 / 20.0);
+        for rate in [44100, 48000] {
+            let loudness = measure_sine(rate, 997.0, amplitude, 5.0);
+            let gain = loudness.gain().unwrap();
+            assert!((gain - 5.0).abs() < 0.1, "{} Hz gain {}", rate, gain);
+            assert!((loudness.peak as f64 - amplitude).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn silence_has_no_gain() {
+        let loudness = measure_sine(44100, 997.0, 0.0, 1.0);
+        assert_eq!(loudness.gain(), None);
+        assert_eq!(loudness.peak, 0.0);
+    }
+
+    #[test]
+    fn album_gates_tracks_together() {
+        let loud = measure_sine(44100, 997.0, 0.5, 3.0);
+        let quiet = measure_sine(44100, 997.0, 0.001, 3.0);
+        let (gain, peak) = Loudness::album(&[&loud, &quiet]);
+        // The quiet track falls below the relative gate
+        assert!((gain.unwrap() - loud.gain().unwrap()).abs() < 0.01);
+        assert_eq!(peak, loud.peak);
+        assert_eq!(format_gain(-2.5), "-2.50 dB");
+        assert_eq!(format_peak(0.5), "0.500000");
+    }
+}
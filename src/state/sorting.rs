@@ -0,0 +1,158 @@
+use std::{fs, time::SystemTime};
+
+use id3::TagLike;
+
+use crate::state::main_state::Entry;
+
+// The value an entry is sorted by. Values starting with a number (track numbers,
+// dates etc.) are compared numerically first so that "10" sorts after "9" and
+// "2/12" sorts before "10/12".
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortValue {
+    Number(u64, String),
+    Text(String),
+    Time(SystemTime),
+}
+
+impl SortValue {
+    // Returns `None` for empty text so that missing values can be sorted last
+    pub fn from_text(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        let digits: String = text.chars().take_while(|c| c.is_ascii_digit()).collect();
+        Some(match digits.parse() {
+            Ok(n) => SortValue::Number(n, text.to_lowercase()),
+            Err(_) => SortValue::Text(text.to_lowercase()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    FileName,
+    Path,
+    Track,
+    Disc,
+    Album,
+    Artist,
+    Date,
+    Modified,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 8] = [
+        SortKey::FileName,
+        SortKey::Path,
+        SortKey::Track,
+        SortKey::Disc,
+        SortKey::Album,
+        SortKey::Artist,
+        SortKey::Date,
+        SortKey::Modified,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortKey::FileName => "Filename",
+            SortKey::Path => "Path",
+            SortKey::Track => "Track",
+            SortKey::Disc => "Disc",
+            SortKey::Album => "Album",
+            SortKey::Artist => "Artist",
+            SortKey::Date => "Date",
+            SortKey::Modified => "Modification time",
+        }
+    }
+
+    pub fn value(&self, entry: &Entry) -> Option<SortValue> {
+        let frame_text = |id: &str| {
+            entry
+                .tag
+                .get(id)
+                .and_then(|f| f.content().text())
+                .and_then(SortValue::from_text)
+        };
+
+        match self {
+            SortKey::FileName => SortValue::from_text(&entry.filename),
+            SortKey::Path => SortValue::from_text(&entry.path.to_string_lossy()),
+            SortKey::Track => frame_text("TRCK"),
+            SortKey::Disc => frame_text("TPOS"),
+            SortKey::Album => frame_text("TALB"),
+            SortKey::Artist => frame_text("TPE1"),
+            SortKey::Date => frame_text("TDRC"),
+            SortKey::Modified => fs::metadata(&entry.path)
+                .and_then(|m| m.modified())
+                .ok()
+                .map(SortValue::Time),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use id3::Tag;
+
+    use super::*;
+
+    fn sorted(values: &[&str]) -> Vec<String> {
+        let mut values: Vec<&str> = values.to_vec();
+        values.sort_by_key(|v| SortValue::from_text(v));
+        values.into_iter().map(|v| v.to_owned()).collect()
+    }
+
+    #[test]
+    fn numbers_sort_numerically() {
+        assert_eq!(sorted(&["10", "9", "1"]), ["1", "9", "10"]);
+        assert_eq!(sorted(&["10/12", "2/12", "02"]), ["02", "2/12", "10/12"]);
+        assert_eq!(
+            sorted(&["2000-05", "1999", "2000-01"]),
+            ["1999", "2000-01", "2000-05"]
+        );
+    }
+
+    #[test]
+    fn numbers_before_text() {
+        assert_eq!(sorted(&["b", "A", "3 songs"]), ["3 songs", "A", "b"]);
+    }
+
+    #[test]
+    fn empty_values_are_missing() {
+        assert_eq!(SortValue::from_text("  "), None);
+        assert_eq!(
+            SortValue::from_text(" Abc "),
+            Some(SortValue::Text("abc".to_owned()))
+        );
+        // Too many digits for a number
+        assert_eq!(
+            SortValue::from_text("99999999999999999999"),
+            Some(SortValue::Text("99999999999999999999".to_owned()))
+        );
+    }
+
+    #[test]
+    fn key_values() {
+        let mut tag = Tag::new();
+        tag.set_text("TRCK", "3/10");
+        tag.set_album("");
+        let entry = Entry::new(PathBuf::from("/music/b/02 Song.mp3"), tag);
+
+        assert_eq!(
+            SortKey::Track.value(&entry),
+            Some(SortValue::Number(3, "3/10".to_owned()))
+        );
+        assert_eq!(
+            SortKey::FileName.value(&entry),
+            Some(SortValue::Number(2, "02 song.mp3".to_owned()))
+        );
+        assert_eq!(SortKey::Album.value(&entry), None);
+        assert_eq!(SortKey::Artist.value(&entry), None);
+        // The file does not exist
+        assert_eq!(SortKey::Modified.value(&entry), None);
+    }
+}
@@ -0,0 +1,219 @@
+use std::path::{Component, Path};
+
+use id3::Frame;
+use regex::Regex;
+
+use crate::state::frame_data;
+
+// Matches the `{...}` tokens used in the rename template and tag patterns
+pub const TOKEN_REGEX: &str = r"\{[\w]+\}";
+
+// A filename pattern such as `{track} - {artist} - {title}.mp3` compiled into a
+// regex. Each `{...}` token becomes a capture group for the matching frame and
+// everything else in the pattern must match literally.
+//
+// Patterns containing `/` also match against the parent directories of a file,
+// `{artist}/{album}/{track} {title}.mp3` matches the filename and the two
+// directories above it.
+pub struct TagPattern {
+    regex: Regex,
+    frame_ids: Vec<String>,
+    components: usize,
+}
+
+impl TagPattern {
+    // `token_rex` is the regex used to find the `{...}` tokens in the template string
+    pub fn new(pattern: &str, token_rex: &Regex) -> Result<Self, String> {
+        let mut expr = String::from("^");
+        let mut frame_ids = vec![];
+        let mut last = 0;
+
+        for mat in token_rex.find_iter(pattern) {
+            let name = &pattern[mat.start() + 1..mat.end() - 1];
+            let id = match frame_data::name_to_id(name) {
+                Ok(id) => id,
+                Err(_) => return Err(format!("Unknown frame id in pattern: '{}'", name)),
+            };
+            if frame_ids.contains(&id) {
+                return Err(format!(
+                    "Frame '{}' appears more than once in pattern",
+                    name
+                ));
+            }
+
+            expr.push_str(&regex::escape(&pattern[last..mat.start()]));
+            // Tokens never match across path components
+            expr.push_str("([^/]+?)");
+            frame_ids.push(id);
+            last = mat.end();
+        }
+        expr.push_str(&regex::escape(&pattern[last..]));
+        expr.push('$');
+
+        if frame_ids.is_empty() {
+            return Err("Pattern does not contain any frames".to_owned());
+        }
+
+        let regex = Regex::new(&expr).map_err(|e| e.to_string())?;
+        Ok(Self {
+            regex,
+            frame_ids,
+            components: pattern.split('/').count(),
+        })
+    }
+
+    pub fn frame_ids(&self) -> &[String] {
+        &self.frame_ids
+    }
+
+    // Header row for a preview of the pattern, the matched text then each frame name
+    pub fn preview_header(&self) -> Vec<String> {
+        let mut header = match self.depth() {
+            0 => vec!["Filename".to_owned()],
+            _ => vec!["Path".to_owned()],
+        };
+        for id in &self.frame_ids {
+            header.push(frame_data::id_to_name(id).unwrap_or_else(|_| id.clone()));
+        }
+        header
+    }
+
+    // Number of directories above the file the pattern matches against
+    pub fn depth(&self) -> usize {
+        self.components - 1
+    }
+
+    // Build the text the pattern is matched against from the parent directories of
+    // `path` below `root` and `filename`, every parent is used when there is no root.
+    // Returns `None` if `path` does not have enough parents below `root`.
+    pub fn subject(&self, root: Option<&Path>, path: &Path, filename: &str) -> Option<String> {
+        let mut parent = path.parent()?;
+        if let Some(root) = root {
+            parent = parent.strip_prefix(root).ok()?;
+        }
+        let mut parts: Vec<String> = parent
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        if parts.len() < self.depth() {
+            return None;
+        }
+        let mut parts = parts.split_off(parts.len() - self.depth());
+        parts.push(filename.to_owned());
+
+        Some(parts.join("/"))
+    }
+
+    // Get the value of each frame in the pattern, in the same order as `frame_ids`,
+    // or `None` if `text` does not match
+    pub fn captures(&self, text: &str) -> Option<Vec<String>> {
+        let caps = self.regex.captures(text)?;
+        let values = self
+            .frame_ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| clean_value(id, &caps[i + 1]))
+            .collect();
+
+        Some(values)
+    }
+
+    // Text frames for the values returned by `captures`, values that are empty after
+    // trimming are skipped so they don't add empty frames
+    pub fn frames(&self, values: &[String]) -> Vec<Frame> {
+        self.frame_ids
+            .iter()
+            .zip(values)
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(id, value)| Frame::text(id.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
+// Trim whitespace and remove the leading zeros added to track and disc numbers
+// when renaming files
+fn clean_value(id: &str, value: &str) -> String {
+    let value = value.trim();
+    match id {
+        "TRCK" | "TPOS" => value
+            .split('/')
+            .map(|n| match n.trim_start_matches('0') {
+                "" if !n.is_empty() => "0",
+                n => n,
+            })
+            .collect::<Vec<&str>>()
+            .join("/"),
+        _ => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(pattern: &str) -> TagPattern {
+        TagPattern::new(pattern, &Regex::new(TOKEN_REGEX).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn captures_frames() {
+        let pattern = pattern("{track} - {artist} - {title}.mp3");
+        assert_eq!(pattern.frame_ids(), ["TRCK", "TPE1", "TIT2"]);
+        let values = pattern.captures("01 - Band - Song.mp3").unwrap();
+        assert_eq!(values, ["1", "Band", "Song"]);
+        assert_eq!(pattern.frames(&values).len(), 3);
+        assert_eq!(pattern.captures("Band - Song.mp3"), None);
+    }
+
+    #[test]
+    fn empty_captures_add_no_frames() {
+        let pattern = pattern("{artist} -{title}.mp3");
+        let values = pattern.captures("Band - .mp3").unwrap();
+        assert_eq!(values, ["Band", ""]);
+        let frames = pattern.frames(&values);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].id(), "TPE1");
+    }
+
+    #[test]
+    fn subject_below_root() {
+        let root = Path::new("/music");
+        let nested = pattern("{artist}/{album}/{title}.mp3");
+        let path = Path::new("/music/Band/Album/Song.mp3");
+        assert_eq!(
+            nested.subject(Some(root), path, "Song.mp3").as_deref(),
+            Some("Band/Album/Song.mp3")
+        );
+        assert_eq!(
+            nested
+                .subject(None, Path::new("/a/b/Band/Album/Song.mp3"), "Song.mp3")
+                .as_deref(),
+            Some("Band/Album/Song.mp3")
+        );
+        // The root directory itself is never matched
+        let path = Path::new("/music/Album/Song.mp3");
+        assert_eq!(nested.subject(Some(root), path, "Song.mp3"), None);
+        assert_eq!(
+            nested.subject(Some(Path::new("/other")), path, "Song.mp3"),
+            None
+        );
+
+        let flat = pattern("{title}.mp3");
+        assert_eq!(
+            flat.subject(Some(root), path, "Song.mp3").as_deref(),
+            Some("Song.mp3")
+        );
+    }
+
+    #[test]
+    fn clean_values() {
+        assert_eq!(clean_value("TRCK", " 01 "), "1");
+        assert_eq!(clean_value("TRCK", "03/12"), "3/12");
+        assert_eq!(clean_value("TPOS", "00"), "0");
+        assert_eq!(clean_value("TRCK", ""), "");
+        assert_eq!(clean_value("TIT2", " 007 "), "007");
+    }
+}
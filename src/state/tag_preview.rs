@@ -0,0 +1,161 @@
+use std::{fs::File, path::Path};
+
+use id3::TagLike;
+
+use crate::state::{backend::backend_for, frame_data};
+
+// Frames shown in the preview, in order
+const PREVIEW_FRAMES: [&str; 8] = [
+    "TIT2", "TPE1", "TALB", "TPE2", "TRCK", "TPOS", "TDRC", "TCON",
+];
+
+// A summary of the tags of a file shown in the files screen before it is added
+#[derive(Debug, Clone)]
+pub struct TagPreview {
+    // Name of the kind of tag read, `None` if the file has no tag
+    pub tag_name: Option<String>,
+    // `None` if the file can not have an ID3v1 tag
+    pub has_v1: Option<bool>,
+    // Name and value of each key frame present in the tag
+    pub frames: Vec<(String, String)>,
+    pub other_frames: usize,
+    pub pictures: usize,
+    pub error: Option<String>,
+}
+
+impl TagPreview {
+    pub fn read(path: &Path) -> Self {
+        let mut preview = Self {
+            tag_name: None,
+            has_v1: None,
+            frames: vec![],
+            other_frames: 0,
+            pictures: 0,
+            error: None,
+        };
+        let backend = match backend_for(path) {
+            Some(backend) => backend,
+            None => {
+                preview.error = Some("Unsupported file type".to_owned());
+                return preview;
+            }
+        };
+
+        if backend.has_id3v1() {
+            preview.has_v1 = Some(
+                File::open(path)
+                    .map(|f| id3::v1::Tag::is_candidate(f).unwrap_or(false))
+                    .unwrap_or(false),
+            );
+        }
+
+        let tag = match backend.read(path) {
+            Ok(tag) => tag,
+            Err(id3::Error {
+                kind: id3::ErrorKind::NoTag,
+                ..
+            }) => return preview,
+            Err(e) => {
+                preview.error = Some(e.to_string());
+                return preview;
+            }
+        };
+
+        preview.tag_name = Some(backend.tag_name(&tag));
+        for id in PREVIEW_FRAMES {
+            if let Some(text) = tag.get(id).and_then(|f| f.content().text()) {
+                let name = frame_data::id_to_name(id).unwrap_or_else(|_| id.to_owned());
+                preview.frames.push((name, text.to_owned()));
+            }
+        }
+        preview.pictures = tag.pictures().count();
+        preview.other_frames = tag.frames().count() - preview.frames.len() - preview.pictures;
+        preview
+    }
+
+    // Lines of text shown in the preview pane
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![match &self.tag_name {
+            Some(name) => name.clone(),
+            None => "No tag".to_owned(),
+        }];
+        match self.has_v1 {
+            Some(true) => lines.push("ID3v1 tag present".to_owned()),
+            Some(false) => lines.push("No ID3v1 tag".to_owned()),
+            None => {}
+        }
+        if let Some(e) = &self.error {
+            lines.push(format!("Could not read tag - {}", e));
+            return lines;
+        }
+
+        lines.push("".to_owned());
+        for (name, value) in &self.frames {
+            lines.push(format!("{}: {}", name, value));
+        }
+        if self.pictures > 0 {
+            lines.push(format!("Cover art: {} picture(s)", self.pictures));
+        }
+        if self.other_frames > 0 {
+            lines.push(format!("{} other frame(s)", self.other_frames));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use id3::{
+        frame::{Picture, PictureType},
+        Tag, Version,
+    };
+
+    use super::*;
+    use crate::state::test_dir::TestDir;
+
+    #[test]
+    fn preview_mp3() {
+        let dir = TestDir::new("preview");
+        let mut v1 = b"TAG".to_vec();
+        v1.resize(128, 0);
+        let path = dir.file("a.mp3", &v1);
+
+        let mut tag = Tag::new();
+        tag.set_title("Song");
+        tag.set_artist("Artist");
+        tag.set_text("TENC", "Encoder");
+        tag.add_frame(Picture {
+            mime_type: "image/png".to_owned(),
+            picture_type: PictureType::CoverFront,
+            description: "".to_owned(),
+            data: vec![1, 2, 3],
+        });
+        tag.write_to_path(&path, Version::Id3v24).unwrap();
+
+        assert_eq!(
+            TagPreview::read(&path).lines(),
+            [
+                "ID3v2.4",
+                "ID3v1 tag present",
+                "",
+                "Title: Song",
+                "Artist: Artist",
+                "Cover art: 1 picture(s)",
+                "1 other frame(s)",
+            ]
+        );
+    }
+
+    #[test]
+    fn preview_without_tag() {
+        let dir = TestDir::new("preview");
+        let path = dir.file("a.mp3", &[0; 16]);
+        assert_eq!(
+            TagPreview::read(&path).lines(),
+            ["No tag", "No ID3v1 tag", ""]
+        );
+
+        let preview = TagPreview::read(Path::new("cover.png"));
+        assert_eq!(preview.error.as_deref(), Some("Unsupported file type"));
+    }
+}
@@ -0,0 +1,37 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// A directory for the files written by one test, removed with everything in it when
+// dropped so files are cleaned up even when an assertion fails
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    // The process id and a counter keep the directories of tests running at the same
+    // time, in this or another test binary, apart
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("rid3-{}-{}-{}", name, process::id(), n));
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    // Write `data` to a file in the directory and return its path
+    pub fn file(&self, name: &str, data: &[u8]) -> PathBuf {
+        let path = self.path.join(name);
+        fs::write(&path, data).unwrap();
+        path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
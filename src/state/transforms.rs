@@ -0,0 +1,158 @@
+use unicode_normalization::UnicodeNormalization;
+
+// Text transforms that can be applied to the frames of many files at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    TitleCase,
+    SentenceCase,
+    UpperCase,
+    LowerCase,
+    Trim,
+    CollapseSpaces,
+    Nfc,
+}
+
+impl Transform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transform::TitleCase => "Title Case",
+            Transform::SentenceCase => "Sentence case",
+            Transform::UpperCase => "UPPER CASE",
+            Transform::LowerCase => "lower case",
+            Transform::Trim => "Trim whitespace",
+            Transform::CollapseSpaces => "Collapse spaces",
+            Transform::Nfc => "Unicode NFC",
+        }
+    }
+
+    // `exceptions` are the words left in lower case by title case unless they are
+    // the first or last word
+    pub fn apply(&self, text: &str, exceptions: &[String]) -> String {
+        match self {
+            Transform::TitleCase => title_case(text, exceptions),
+            Transform::SentenceCase => sentence_case(text),
+            Transform::UpperCase => text.to_uppercase(),
+            Transform::LowerCase => text.to_lowercase(),
+            Transform::Trim => text.trim().to_owned(),
+            Transform::CollapseSpaces => collapse_spaces(text),
+            Transform::Nfc => text.nfc().collect(),
+        }
+    }
+}
+
+// Upper case the first letter of `word` and lower case the rest, any leading
+// punctuation such as `(` is skipped
+fn capitalise(word: &str) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut found = false;
+    for c in word.chars() {
+        if !found && c.is_alphanumeric() {
+            result.extend(c.to_uppercase());
+            found = true;
+        } else {
+            result.extend(c.to_lowercase());
+        }
+    }
+    result
+}
+
+fn title_case(text: &str, exceptions: &[String]) -> String {
+    let words: Vec<&str> = text.split(' ').collect();
+    let last = words.iter().rposition(|w| !w.is_empty()).unwrap_or(0);
+    let first = words.iter().position(|w| !w.is_empty()).unwrap_or(0);
+
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let lower = word.to_lowercase();
+            let bare = lower.trim_matches(|c: char| !c.is_alphanumeric());
+            if i != first && i != last && exceptions.iter().any(|e| e.to_lowercase() == bare) {
+                lower
+            } else {
+                capitalise(word)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Lower case everything except the first letter of each sentence
+fn sentence_case(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut capitalise_next = true;
+    for c in text.chars() {
+        if capitalise_next && c.is_alphanumeric() {
+            result.extend(c.to_uppercase());
+            capitalise_next = false;
+        } else {
+            result.extend(c.to_lowercase());
+            if matches!(c, '.' | '!' | '?') {
+                capitalise_next = true;
+            }
+        }
+    }
+    result
+}
+
+// Replace each run of whitespace with a single space
+fn collapse_spaces(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut prev_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !prev_space {
+                result.push(' ');
+            }
+            prev_space = true;
+        } else {
+            result.push(c);
+            prev_space = false;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exceptions() -> Vec<String> {
+        ["a", "of", "the"].iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn title_case_exceptions() {
+        let apply = |text| Transform::TitleCase.apply(text, &exceptions());
+        assert_eq!(apply("the best OF the rest"), "The Best of the Rest");
+        // The first and last words are always capitalised, even after spaces
+        assert_eq!(apply("  of mice and the"), "  Of Mice And The");
+        assert_eq!(apply("(the) end of"), "(The) End Of");
+        assert_eq!(apply("don't stop"), "Don't Stop");
+        assert_eq!(apply(""), "");
+    }
+
+    #[test]
+    fn sentence_case() {
+        let apply = |text| Transform::SentenceCase.apply(text, &[]);
+        assert_eq!(
+            apply("HELLO THERE. how ARE you? 2nd"),
+            "Hello there. How are you? 2nd"
+        );
+        assert_eq!(apply("...and then"), "...And then");
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(Transform::Trim.apply("\t a  b \n", &[]), "a  b");
+        assert_eq!(Transform::CollapseSpaces.apply(" a \t\n b  ", &[]), " a b ");
+    }
+
+    #[test]
+    fn case_and_normalisation() {
+        assert_eq!(Transform::UpperCase.apply("straße", &[]), "STRASSE");
+        assert_eq!(Transform::LowerCase.apply("ÀB", &[]), "àb");
+        // A followed by a combining grave accent
+        assert_eq!(Transform::Nfc.apply("A\u{300}", &[]), "\u{c0}");
+    }
+}
@@ -0,0 +1,182 @@
+use id3::{Content, Frame, Tag, TagLike};
+use log::error;
+use regex::Regex;
+
+use crate::{
+    configuration::{validation::ValidationMode, Config},
+    state::find_replace::parse_frame_ids,
+};
+
+// Rules from the `validation` section of the config that every tag is checked against
+// before it is written
+#[derive(Default)]
+pub struct Rules {
+    pub mode: ValidationMode,
+    required: Vec<String>,
+    // Frame id, the regex matching the whole value and the pattern from the config
+    formats: Vec<(String, Regex, String)>,
+    max_lengths: Vec<(String, usize)>,
+}
+
+impl Rules {
+    // Invalid regexes are logged and left out
+    pub fn new(config: &Config) -> Self {
+        let mut formats = vec![];
+        let mut config_formats: Vec<(String, String)> =
+            config.get_frame_formats().into_iter().collect();
+        config_formats.sort();
+        for (frames, pattern) in config_formats {
+            match Regex::new(&format!("^(?:{})$", pattern)) {
+                Ok(regex) => {
+                    for id in parse_frame_ids(&frames) {
+                        formats.push((id, regex.clone(), pattern.clone()));
+                    }
+                }
+                Err(e) => error!("Invalid validation format for {} - {}", frames, e),
+            }
+        }
+
+        let mut max_lengths = vec![];
+        let mut config_lengths: Vec<(String, usize)> =
+            config.get_max_lengths().into_iter().collect();
+        config_lengths.sort();
+        for (frames, max) in config_lengths {
+            for id in parse_frame_ids(&frames) {
+                max_lengths.push((id, max));
+            }
+        }
+
+        Self {
+            mode: config.get_validation_mode(),
+            required: parse_frame_ids(&config.get_required_frames().join(",")),
+            formats,
+            max_lengths,
+        }
+    }
+
+    // Describe every rule broken by `tag`, nothing is checked when validation is off
+    pub fn check(&self, tag: &Tag) -> Vec<String> {
+        let mut problems = vec![];
+        if self.mode == ValidationMode::Off {
+            return problems;
+        }
+
+        for id in &self.required {
+            if tag.get(id).is_none() {
+                problems.push(format!("Required frame {} is missing", id));
+            }
+        }
+
+        for frame in tag.frames() {
+            let text = match frame_text(frame) {
+                Some(text) => text,
+                None => continue,
+            };
+            // Frames with several values are joined with null characters
+            for value in text.split('\0') {
+                for (_, regex, pattern) in self.formats.iter().filter(|(id, _, _)| id == frame.id())
+                {
+                    if !regex.is_match(value) {
+                        problems.push(format!(
+                            "{} value \"{}\" does not match the format {}",
+                            frame.id(),
+                            value,
+                            pattern
+                        ));
+                    }
+                }
+                for (_, max) in self.max_lengths.iter().filter(|(id, _)| id == frame.id()) {
+                    let len = value.chars().count();
+                    if len > *max {
+                        problems.push(format!(
+                            "{} is {} characters long, the maximum is {}",
+                            frame.id(),
+                            len,
+                            max
+                        ));
+                    }
+                }
+            }
+        }
+        problems
+    }
+}
+
+// The text checked by the rules, `None` for frames without text such as pictures
+fn frame_text(frame: &Frame) -> Option<&str> {
+    match frame.content() {
+        Content::Text(text) | Content::Link(text) => Some(text),
+        Content::ExtendedText(text) => Some(&text.value),
+        Content::Comment(comment) => Some(&comment.text),
+        Content::Lyrics(lyrics) => Some(&lyrics.text),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(mode: ValidationMode) -> Rules {
+        let regex = |pattern: &str| Regex::new(&format!("^(?:{})$", pattern)).unwrap();
+        Rules {
+            mode,
+            required: vec!["TIT2".to_owned(), "TPE1".to_owned()],
+            formats: vec![(
+                "TRCK".to_owned(),
+                regex(r"\d+(/\d+)?"),
+                r"\d+(/\d+)?".to_owned(),
+            )],
+            max_lengths: vec![("TPE1".to_owned(), 5)],
+        }
+    }
+
+    fn tag(frames: &[(&str, &str)]) -> Tag {
+        let mut tag = Tag::new();
+        for (id, text) in frames {
+            tag.add_frame(Frame::text(*id, *text));
+        }
+        tag
+    }
+
+    #[test]
+    fn required_frames() {
+        let rules = rules(ValidationMode::Warn);
+        assert_eq!(
+            rules.check(&tag(&[("TPE1", "Band")])),
+            ["Required frame TIT2 is missing"]
+        );
+        assert!(rules
+            .check(&tag(&[("TIT2", "Song"), ("TPE1", "Band")]))
+            .is_empty());
+    }
+
+    #[test]
+    fn formats_and_max_lengths() {
+        let rules = rules(ValidationMode::Block);
+        let tag = tag(&[("TIT2", "Song"), ("TPE1", "Bandname"), ("TRCK", "1 of 9")]);
+        assert_eq!(
+            rules.check(&tag),
+            [
+                "TPE1 is 8 characters long, the maximum is 5",
+                "TRCK value \"1 of 9\" does not match the format \\d+(/\\d+)?",
+            ]
+        );
+    }
+
+    #[test]
+    fn values_are_checked_separately() {
+        let rules = rules(ValidationMode::Warn);
+        let tag = tag(&[("TIT2", "Song"), ("TPE1", "One\0Three\0Eleven")]);
+        assert_eq!(
+            rules.check(&tag),
+            ["TPE1 is 6 characters long, the maximum is 5"]
+        );
+    }
+
+    #[test]
+    fn nothing_is_checked_when_off() {
+        let rules = rules(ValidationMode::Off);
+        assert!(rules.check(&tag(&[("TRCK", "x")])).is_empty());
+    }
+}
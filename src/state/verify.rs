@@ -0,0 +1,261 @@
+use std::{fs, io, path::Path};
+
+use crate::state::{
+    backend::backend_for,
+    mpeg::{find_frame, header_at, trailing_tags_len},
+};
+
+// Sync errors reported one by one before they are only counted
+const MAX_SYNC_ERRORS: usize = 5;
+
+// Check that a file can be tagged and, for mp3 files, that the tag and audio frames
+// are intact. Returns a description of each problem found.
+pub fn verify(path: &Path) -> Vec<String> {
+    let backend = match backend_for(path) {
+        Some(backend) => backend,
+        None => return vec!["Unsupported file type".to_owned()],
+    };
+
+    let mut problems = vec![];
+    if let Err(e) = backend.read(path) {
+        if !matches!(e.kind, id3::ErrorKind::NoTag) {
+            problems.push(format!("Tag can not be read - {}", e));
+        }
+    }
+    match backend.verify_stream(path) {
+        Ok(mut stream_problems) => problems.append(&mut stream_problems),
+        Err(e) => problems.push(format!("Could not read file - {}", e)),
+    }
+    problems
+}
+
+// Walk the ID3v2 tag and every MPEG frame of an mp3 file
+pub fn verify_mpeg(path: &Path) -> io::Result<Vec<String>> {
+    let data = fs::read(path)?;
+    let mut problems = vec![];
+
+    let audio_start = match check_id3v2(&data, &mut problems) {
+        Some(len) => len,
+        None => return Ok(problems),
+    };
+
+    // Tags at the end of the file are not part of the audio
+    let audio_end = data.len() - trailing_tags_len(&data[audio_start..]);
+    let audio = &data[audio_start..audio_end];
+
+    let (first, header) = match find_frame(audio) {
+        Some(found) => found,
+        None => {
+            problems.push("No MPEG audio frames found".to_owned());
+            return Ok(problems);
+        }
+    };
+    if first > 0 {
+        problems.push(format!(
+            "{} bytes of junk between the tag and the first frame",
+            first
+        ));
+    }
+
+    let mut pos = first;
+    let mut sync_errors = 0;
+    while pos < audio.len() {
+        match header_at(audio, pos) {
+            Some(next) if header.matches(&next) => {
+                let end = pos + next.len();
+                if end > audio.len() {
+                    problems.push(format!(
+                        "Last frame is truncated, {} of {} bytes present",
+                        audio.len() - pos,
+                        next.len()
+                    ));
+                }
+                pos = end;
+            }
+            _ => {
+                let skipped = match find_frame(&audio[pos + 1..]) {
+                    Some((offset, _)) => offset + 1,
+                    None => {
+                        problems.push(format!(
+                            "{} bytes of junk after the last frame",
+                            audio.len() - pos
+                        ));
+                        break;
+                    }
+                };
+                sync_errors += 1;
+                if sync_errors <= MAX_SYNC_ERRORS {
+                    problems.push(format!(
+                        "Lost sync at byte {}, skipped {} bytes",
+                        audio_start + pos,
+                        skipped
+                    ));
+                }
+                pos += skipped;
+            }
+        }
+    }
+    if sync_errors > MAX_SYNC_ERRORS {
+        problems.push(format!("{} sync errors in total", sync_errors));
+    }
+
+    Ok(problems)
+}
+
+// Check the size, frames and padding of the ID3v2 tag at the start of `data`. Returns
+// the length of the tag, zero if there is none, or `None` if the audio can't be found.
+fn check_id3v2(data: &[u8], problems: &mut Vec<String>) -> Option<usize> {
+    if data.len() < 10 || &data[..3] != b"ID3" {
+        return Some(0);
+    }
+
+    let version = data[3];
+    if !(2..=4).contains(&version) {
+        problems.push(format!("Unknown ID3v2 version 2.{}", version));
+        return None;
+    }
+    if data[6..10].iter().any(|b| b & 0x80 != 0) {
+        problems.push("ID3v2 tag size is not a syncsafe integer".to_owned());
+    }
+    let size = syncsafe(&data[6..10]);
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+    let tag_len = 10 + size + footer;
+    if tag_len > data.len() {
+        problems.push(format!(
+            "ID3v2 tag size of {} bytes runs past the end of the file",
+            size
+        ));
+        return None;
+    }
+
+    // Unsynchronised and extended header tags are not walked
+    if data[5] & 0xc0 != 0 {
+        return Some(tag_len);
+    }
+
+    let body = &data[10..10 + size];
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut pos = 0;
+    while pos + header_len <= body.len() {
+        let id = &body[pos..pos + id_len];
+        if id[0] == 0 {
+            break;
+        }
+        if !id
+            .iter()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        {
+            problems.push(format!(
+                "Invalid ID3v2 frame id at byte {}, the tag size or a frame size is wrong",
+                10 + pos
+            ));
+            return Some(tag_len);
+        }
+
+        let frame_size = match version {
+            2 => u32::from_be_bytes([0, body[pos + 3], body[pos + 4], body[pos + 5]]) as usize,
+            3 => u32::from_be_bytes(body[pos + 4..pos + 8].try_into().unwrap()) as usize,
+            _ => {
+                if body[pos + 4..pos + 8].iter().any(|b| b & 0x80 != 0) {
+                    problems.push(format!(
+                        "ID3v2.4 frame {} size is not a syncsafe integer",
+                        String::from_utf8_lossy(id)
+                    ));
+                }
+                syncsafe(&body[pos + 4..pos + 8])
+            }
+        };
+        pos += header_len + frame_size;
+        if pos > body.len() {
+            problems.push(format!(
+                "ID3v2 frame {} runs past the end of the tag",
+                String::from_utf8_lossy(id)
+            ));
+            return Some(tag_len);
+        }
+    }
+
+    if body[pos.min(body.len())..].iter().any(|b| *b != 0) {
+        problems.push("ID3v2 padding contains non-zero bytes".to_owned());
+    }
+    Some(tag_len)
+}
+
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |size, b| (size << 7) | (*b & 0x7f) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_dir::TestDir;
+
+    // MPEG-1 Layer III, 128 kbps, 44100 Hz frames of 417 bytes
+    fn frame() -> Vec<u8> {
+        let mut frame = vec![0; 417];
+        frame[..4].copy_from_slice(&[0xff, 0xfb, 0x90, 0x64]);
+        frame
+    }
+
+    // An ID3v2.4 tag holding `frames` followed by `padding` bytes
+    fn id3v2(frames: &[u8], padding: &[u8]) -> Vec<u8> {
+        let size = frames.len() + padding.len();
+        let mut tag = b"ID3\x04\0\0".to_vec();
+        tag.extend((0..4).rev().map(|i| (size >> (7 * i)) as u8 & 0x7f));
+        tag.extend_from_slice(frames);
+        tag.extend_from_slice(padding);
+        tag
+    }
+
+    fn verify_bytes(data: &[u8]) -> Vec<String> {
+        let dir = TestDir::new("verify");
+        verify_mpeg(&dir.file("a.mp3", data)).unwrap()
+    }
+
+    #[test]
+    fn clean_file() {
+        let mut data = id3v2(b"TIT2\0\0\0\x03\0\x03ab", &[0; 20]);
+        data.extend(frame());
+        data.extend(frame());
+        assert_eq!(verify_bytes(&data), Vec::<String>::new());
+    }
+
+    #[test]
+    fn damaged_tag() {
+        let mut problems = vec![];
+        let data = id3v2(b"TIT2\0\0\0\x10\0\x03ab", &[]);
+        assert_eq!(check_id3v2(&data, &mut problems), Some(data.len()));
+        assert_eq!(problems, ["ID3v2 frame TIT2 runs past the end of the tag"]);
+
+        let mut problems = vec![];
+        let data = id3v2(b"", &[0, 0, 1]);
+        check_id3v2(&data, &mut problems);
+        assert_eq!(problems, ["ID3v2 padding contains non-zero bytes"]);
+
+        let mut problems = vec![];
+        let mut data = id3v2(b"", &[0; 4]);
+        data.truncate(12);
+        assert_eq!(check_id3v2(&data, &mut problems), None);
+    }
+
+    #[test]
+    fn damaged_stream() {
+        let mut data = vec![1; 3];
+        data.extend(frame());
+        data.extend(frame());
+        data.extend(vec![2; 10]);
+        data.extend(frame());
+        data.extend(frame());
+        data.extend(&frame()[..100]);
+        assert_eq!(
+            verify_bytes(&data),
+            [
+                "3 bytes of junk between the tag and the first frame",
+                "Lost sync at byte 837, skipped 10 bytes",
+                "Last frame is truncated, 100 of 417 bytes present",
+            ]
+        );
+    }
+}
@@ -0,0 +1,564 @@
+use id3::{
+    frame::{Comment, ExtendedText, Picture, PictureType},
+    Content, Error, ErrorKind, Frame, Tag, TagLike,
+};
+use log::warn;
+
+// Vendor string written when a file has no comment header yet
+const VENDOR: &str = "rid3";
+
+// Vorbis comment fields and the ID3 frames they are shown as, so the same display
+// names, templates and bulk edits work for every kind of file. Track and disc totals
+// are merged into TRCK and TPOS as `n/total`.
+const FIELDS: [(&str, &str); 16] = [
+    ("TIT1", "GROUPING"),
+    ("TIT2", "TITLE"),
+    ("TIT3", "SUBTITLE"),
+    ("TALB", "ALBUM"),
+    ("TPE1", "ARTIST"),
+    ("TPE2", "ALBUMARTIST"),
+    ("TPE3", "CONDUCTOR"),
+    ("TRCK", "TRACKNUMBER"),
+    ("TPOS", "DISCNUMBER"),
+    ("TDRC", "DATE"),
+    ("TCON", "GENRE"),
+    ("TCOM", "COMPOSER"),
+    ("TEXT", "LYRICIST"),
+    ("TCOP", "COPYRIGHT"),
+    ("TSRC", "ISRC"),
+    ("TBPM", "BPM"),
+];
+
+// The comment header shared by FLAC, Ogg Vorbis and Opus files: a vendor string
+// followed by `KEY=value` fields. Keys are case-insensitive and may repeat.
+#[derive(Debug, Clone, Default)]
+pub struct VorbisComments {
+    pub vendor: String,
+    pub fields: Vec<(String, String)>,
+    // Data after the fields, the framing bit of Vorbis headers or binary data
+    // some Opus encoders keep there
+    pub trailing: Vec<u8>,
+}
+
+impl VorbisComments {
+    pub fn parse(data: &[u8]) -> id3::Result<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        let vendor_len = reader.u32_le()? as usize;
+        let vendor = String::from_utf8_lossy(reader.bytes(vendor_len)?).into_owned();
+
+        let count = reader.u32_le()?;
+        let mut fields = vec![];
+        for _ in 0..count {
+            let len = reader.u32_le()? as usize;
+            let field = String::from_utf8_lossy(reader.bytes(len)?).into_owned();
+            match field.split_once('=') {
+                Some((key, value)) => fields.push((key.to_owned(), value.to_owned())),
+                None => warn!("Ignoring vorbis comment without a `=` - {}", field),
+            }
+        }
+
+        let trailing = data[reader.pos..].to_vec();
+        Ok(Self {
+            vendor,
+            fields,
+            trailing,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.vendor.as_bytes());
+        data.extend_from_slice(&(self.fields.len() as u32).to_le_bytes());
+        for (key, value) in &self.fields {
+            let field = format!("{}={}", key, value);
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+        data.extend_from_slice(&self.trailing);
+        data
+    }
+
+    // Values of a field, in file order
+    fn values(&self, key: &str) -> Vec<&str> {
+        self.fields
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    // Convert the comments to an ID3 tag, fields without a matching frame become
+    // TXXX frames. Repeated fields are joined with a null byte as in ID3v2.4.
+    pub fn to_tag(&self) -> Tag {
+        let mut tag = Tag::new();
+        let mut added: Vec<String> = vec![];
+        for (key, _) in &self.fields {
+            let key_upper = key.to_uppercase();
+            if added.contains(&key_upper) {
+                continue;
+            }
+            added.push(key_upper.clone());
+            let value = self.values(key).join("\0");
+
+            match key_upper.as_str() {
+                "TRACKTOTAL" | "TOTALTRACKS" | "DISCTOTAL" | "TOTALDISCS" => {}
+                "ALBUM ARTIST" => tag.set_text("TPE2", value),
+                "COMMENT" | "DESCRIPTION" => {
+                    tag.add_frame(Comment {
+                        lang: "eng".to_owned(),
+                        description: "".to_owned(),
+                        text: value,
+                    });
+                }
+                "METADATA_BLOCK_PICTURE" => {}
+                _ => match FIELDS.iter().find(|(_, k)| *k == key_upper) {
+                    Some((id, _)) => tag.set_text(*id, value),
+                    None => {
+                        tag.add_frame(ExtendedText {
+                            description: key.clone(),
+                            value,
+                        });
+                    }
+                },
+            }
+        }
+
+        self.merge_total(&mut tag, "TRCK", &["TRACKTOTAL", "TOTALTRACKS"]);
+        self.merge_total(&mut tag, "TPOS", &["DISCTOTAL", "TOTALDISCS"]);
+        tag
+    }
+
+    fn merge_total(&self, tag: &mut Tag, id: &str, keys: &[&str]) {
+        let total = match keys.iter().find_map(|k| self.values(k).first().copied()) {
+            Some(total) => total.to_owned(),
+            None => return,
+        };
+        let number = tag.get(id).and_then(|f| f.content().text()).unwrap_or("");
+        if !number.contains('/') {
+            let text = format!("{}/{}", number, total);
+            tag.set_text(id, text);
+        }
+    }
+
+    // Update the fields to match the frames of `tag`. Only the fields of frames that
+    // were changed or removed are replaced, so unchanged fields keep their original
+    // keys and order and fields without a frame are kept. Frames that have no vorbis
+    // comment equivalent are skipped with a warning.
+    pub fn set_from_tag(&mut self, tag: &Tag) {
+        if self.vendor.is_empty() {
+            self.vendor = VENDOR.to_owned();
+        }
+
+        let old = self.to_tag();
+        let mut groups: Vec<Group> = vec![];
+        for frame in old.frames().chain(tag.frames()) {
+            if let Some(group) = Group::of(frame) {
+                if !groups.contains(&group) {
+                    groups.push(group);
+                }
+            }
+        }
+
+        for group in groups {
+            let old_frames: Vec<&Frame> = old.frames().filter(|f| group.contains(f)).collect();
+            let new_frames: Vec<&Frame> = tag.frames().filter(|f| group.contains(f)).collect();
+            if old_frames == new_frames {
+                continue;
+            }
+
+            let mut fields = vec![];
+            for frame in new_frames {
+                frame_fields(frame, &mut fields);
+            }
+            // New fields take the place of the first field they replace
+            let keys = group.keys();
+            let is_replaced =
+                |(key, _): &(String, String)| keys.iter().any(|k| k.eq_ignore_ascii_case(key));
+            let pos = self.fields.iter().position(is_replaced);
+            self.fields.retain(|field| !is_replaced(field));
+            let pos = pos.unwrap_or(self.fields.len()).min(self.fields.len());
+            self.fields.splice(pos..pos, fields);
+        }
+    }
+
+    // Pictures stored base64 encoded in METADATA_BLOCK_PICTURE fields, as Ogg files
+    // have no separate picture blocks
+    pub fn pictures(&self) -> Vec<Picture> {
+        self.values("METADATA_BLOCK_PICTURE")
+            .into_iter()
+            .filter_map(|value| {
+                let picture = base64_decode(value).and_then(|data| parse_picture(&data).ok());
+                if picture.is_none() {
+                    warn!("Ignoring invalid METADATA_BLOCK_PICTURE comment");
+                }
+                picture
+            })
+            .collect()
+    }
+
+    // Replace the METADATA_BLOCK_PICTURE fields with the pictures of `tag`, they are
+    // left as they are if the pictures did not change
+    pub fn set_pictures(&mut self, tag: &Tag) {
+        if self.pictures().iter().eq(tag.pictures()) {
+            return;
+        }
+        self.fields
+            .retain(|(key, _)| !key.eq_ignore_ascii_case("METADATA_BLOCK_PICTURE"));
+        for picture in tag.pictures() {
+            let value = base64_encode(&picture_bytes(picture));
+            self.fields
+                .push(("METADATA_BLOCK_PICTURE".to_owned(), value));
+        }
+    }
+}
+
+// The frames a set of fields is shown as, fields are replaced a group at a time
+#[derive(Debug, PartialEq)]
+enum Group {
+    Text(String),
+    ExtendedText(String),
+    Comment,
+}
+
+impl Group {
+    fn of(frame: &Frame) -> Option<Self> {
+        match frame.content() {
+            Content::Text(_) => Some(Group::Text(frame.id().to_owned())),
+            Content::ExtendedText(ext) => Some(Group::ExtendedText(ext.description.clone())),
+            Content::Comment(_) => Some(Group::Comment),
+            _ => None,
+        }
+    }
+
+    fn contains(&self, frame: &Frame) -> bool {
+        Group::of(frame).as_ref() == Some(self)
+    }
+
+    // Keys of the fields read into the group's frames
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            Group::Text(id) => match id.as_str() {
+                "TRCK" => vec!["TRACKNUMBER", "TRACKTOTAL", "TOTALTRACKS"],
+                "TPOS" => vec!["DISCNUMBER", "DISCTOTAL", "TOTALDISCS"],
+                "TPE2" => vec!["ALBUMARTIST", "ALBUM ARTIST"],
+                id => FIELDS
+                    .iter()
+                    .filter(|(i, _)| *i == id)
+                    .map(|(_, key)| *key)
+                    .collect(),
+            },
+            Group::ExtendedText(description) => vec![description.as_str()],
+            Group::Comment => vec!["COMMENT", "DESCRIPTION"],
+        }
+    }
+}
+
+// Add the fields `frame` is saved as to `fields`
+fn frame_fields(frame: &Frame, fields: &mut Vec<(String, String)>) {
+    match frame.content() {
+        Content::Text(text) => {
+            let key = match FIELDS.iter().find(|(id, _)| *id == frame.id()) {
+                Some((_, key)) => *key,
+                None => {
+                    warn!("{} frames can not be saved as vorbis comments", frame.id());
+                    return;
+                }
+            };
+            match frame.id() {
+                "TRCK" => push_total(fields, text, "TRACKNUMBER", "TRACKTOTAL"),
+                "TPOS" => push_total(fields, text, "DISCNUMBER", "DISCTOTAL"),
+                _ => push(fields, key, text),
+            }
+        }
+        Content::ExtendedText(ext) => push(fields, &ext.description, &ext.value),
+        Content::Comment(comment) => push(fields, "COMMENT", &comment.text),
+        Content::Picture(_) => {}
+        _ => warn!("{} frames can not be saved as vorbis comments", frame.id()),
+    }
+}
+
+// Add a field for every null separated value of `text`
+fn push(fields: &mut Vec<(String, String)>, key: &str, text: &str) {
+    for value in text.split('\0') {
+        fields.push((key.to_owned(), value.to_owned()));
+    }
+}
+
+fn push_total(fields: &mut Vec<(String, String)>, text: &str, number_key: &str, total_key: &str) {
+    match text.split_once('/') {
+        Some((number, total)) => {
+            if !number.is_empty() {
+                push(fields, number_key, number);
+            }
+            push(fields, total_key, total);
+        }
+        None => push(fields, number_key, text),
+    }
+}
+
+// Parse a FLAC PICTURE block, also used base64 encoded in the
+// METADATA_BLOCK_PICTURE comment of Ogg files
+pub fn parse_picture(data: &[u8]) -> id3::Result<Picture> {
+    let mut reader = Reader { data, pos: 0 };
+    let picture_type = picture_type(reader.u32_be()?);
+    let mime_len = reader.u32_be()? as usize;
+    let mime_type = String::from_utf8_lossy(reader.bytes(mime_len)?).into_owned();
+    let description_len = reader.u32_be()? as usize;
+    let description = String::from_utf8_lossy(reader.bytes(description_len)?).into_owned();
+    // Width, height, colour depth and number of colours
+    reader.bytes(16)?;
+    let data_len = reader.u32_be()? as usize;
+    let data = reader.bytes(data_len)?.to_vec();
+
+    Ok(Picture {
+        mime_type,
+        picture_type,
+        description,
+        data,
+    })
+}
+
+// Encode a picture as a FLAC PICTURE block. The image dimensions are not known so
+// they are written as zero.
+pub fn picture_bytes(picture: &Picture) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&(u8::from(picture.picture_type) as u32).to_be_bytes());
+    data.extend_from_slice(&(picture.mime_type.len() as u32).to_be_bytes());
+    data.extend_from_slice(picture.mime_type.as_bytes());
+    data.extend_from_slice(&(picture.description.len() as u32).to_be_bytes());
+    data.extend_from_slice(picture.description.as_bytes());
+    data.extend_from_slice(&[0; 16]);
+    data.extend_from_slice(&(picture.data.len() as u32).to_be_bytes());
+    data.extend_from_slice(&picture.data);
+    data
+}
+
+fn picture_type(value: u32) -> PictureType {
+    match value {
+        0 => PictureType::Other,
+        1 => PictureType::Icon,
+        2 => PictureType::OtherIcon,
+        3 => PictureType::CoverFront,
+        4 => PictureType::CoverBack,
+        5 => PictureType::Leaflet,
+        6 => PictureType::Media,
+        7 => PictureType::LeadArtist,
+        8 => PictureType::Artist,
+        9 => PictureType::Conductor,
+        10 => PictureType::Band,
+        11 => PictureType::Composer,
+        12 => PictureType::Lyricist,
+        13 => PictureType::RecordingLocation,
+        14 => PictureType::DuringRecording,
+        15 => PictureType::DuringPerformance,
+        16 => PictureType::ScreenCapture,
+        17 => PictureType::BrightFish,
+        18 => PictureType::Illustration,
+        19 => PictureType::BandLogo,
+        20 => PictureType::PublisherLogo,
+        v => PictureType::Undefined(v.min(u8::MAX as u32) as u8),
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => text.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => text.push('='),
+            }
+        }
+    }
+    text
+}
+
+// `None` if `text` is not valid base64
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut data = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = BASE64.iter().position(|b| b == c)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        data.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(data)
+}
+
+// Reads integers and byte strings from a buffer, running past the end is a parsing
+// error rather than a panic
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> id3::Result<&'a [u8]> {
+        match self.data.get(self.pos..self.pos.saturating_add(len)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(Error::new(ErrorKind::Parsing, "Unexpected end of metadata")),
+        }
+    }
+
+    fn u32_le(&mut self) -> id3::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u32_be(&mut self) -> id3::Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+}
+
+// Add `pictures` to `tag` as APIC frames
+pub fn add_pictures(tag: &mut Tag, pictures: Vec<Picture>) {
+    for picture in pictures {
+        tag.add_frame(Frame::with_content("APIC", Content::Picture(picture)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comments(fields: &[(&str, &str)]) -> VorbisComments {
+        VorbisComments {
+            vendor: "test".to_owned(),
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            trailing: vec![],
+        }
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let original = comments(&[("TITLE", "Song"), ("ARTIST", "A"), ("ARTIST", "B")]);
+        let parsed = VorbisComments::parse(&original.to_bytes()).unwrap();
+        assert_eq!(parsed.vendor, "test");
+        assert_eq!(parsed.fields, original.fields);
+    }
+
+    #[test]
+    fn parse_keeps_trailing_data() {
+        let mut original = comments(&[("TITLE", "Song")]);
+        original.trailing = vec![1, 0xff, 0];
+        let parsed = VorbisComments::parse(&original.to_bytes()).unwrap();
+        assert_eq!(parsed.trailing, original.trailing);
+        assert_eq!(parsed.to_bytes(), original.to_bytes());
+    }
+
+    #[test]
+    fn parse_truncated() {
+        let bytes = comments(&[("TITLE", "Song")]).to_bytes();
+        assert!(VorbisComments::parse(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn to_tag_merges_fields() {
+        let tag = comments(&[
+            ("title", "Song"),
+            ("ARTIST", "A"),
+            ("ARTIST", "B"),
+            ("TRACKNUMBER", "3"),
+            ("TOTALTRACKS", "12"),
+            ("MOOD", "Calm"),
+        ])
+        .to_tag();
+        assert_eq!(tag.title(), Some("Song"));
+        assert_eq!(tag.artist(), Some("A\0B"));
+        assert_eq!(
+            tag.get("TRCK").and_then(|f| f.content().text()),
+            Some("3/12")
+        );
+        assert_eq!(tag.extended_texts().next().unwrap().value, "Calm");
+    }
+
+    #[test]
+    fn set_from_tag_unchanged() {
+        let fields = [
+            ("title", "Song"),
+            ("TRACKNUMBER", "3"),
+            ("TOTALTRACKS", "12"),
+            ("Mood", "Calm"),
+            ("METADATA_BLOCK_PICTURE", "AAAA"),
+        ];
+        let mut vorbis = comments(&fields);
+        let tag = vorbis.to_tag();
+        vorbis.set_from_tag(&tag);
+        assert_eq!(vorbis.fields, comments(&fields).fields);
+    }
+
+    #[test]
+    fn set_from_tag_replaces_changed() {
+        let mut vorbis = comments(&[
+            ("ALBUM ARTIST", "A"),
+            ("title", "Song"),
+            ("TRACKNUMBER", "3"),
+            ("TOTALTRACKS", "12"),
+            ("COMMENT", "Old"),
+        ]);
+        let mut tag = vorbis.to_tag();
+        tag.set_text("TIT2", "New\0Song");
+        tag.set_text("TRCK", "4/12");
+        tag.remove("COMM");
+        tag.set_text("TPE1", "B");
+        vorbis.set_from_tag(&tag);
+        assert_eq!(
+            vorbis.fields,
+            comments(&[
+                ("ALBUM ARTIST", "A"),
+                ("TITLE", "New"),
+                ("TITLE", "Song"),
+                ("TRACKNUMBER", "4"),
+                ("TRACKTOTAL", "12"),
+                ("ARTIST", "B"),
+            ])
+            .fields
+        );
+    }
+
+    #[test]
+    fn set_pictures_round_trip() {
+        let mut tag = Tag::new();
+        tag.add_frame(Picture {
+            mime_type: "image/png".to_owned(),
+            picture_type: PictureType::CoverFront,
+            description: "cover".to_owned(),
+            data: vec![1, 2, 3, 4, 5],
+        });
+        let mut vorbis = comments(&[("TITLE", "Song")]);
+        vorbis.set_pictures(&tag);
+        vorbis.set_pictures(&tag);
+        assert_eq!(vorbis.fields.len(), 2);
+        assert!(vorbis.pictures().iter().eq(tag.pictures()));
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for len in 0..8 {
+            let data: Vec<u8> = (0..len).map(|i| i * 37).collect();
+            assert_eq!(base64_decode(&base64_encode(&data)), Some(data));
+        }
+        assert_eq!(base64_encode(b"ab"), "YWI=");
+        assert_eq!(base64_decode("*"), None);
+    }
+}